@@ -0,0 +1,155 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use my_azure_page_blob_ext::{PagesCacheIntervals, PagesCacheItem};
+
+const PAGE_SIZE: usize = 512;
+
+/// Previous implementation: every lookup and insert scans the whole Vec of intervals
+struct LinearPagesCacheIntervals {
+    pages: Vec<PagesCacheItem>,
+}
+
+impl LinearPagesCacheIntervals {
+    fn new() -> Self {
+        Self { pages: Vec::new() }
+    }
+
+    fn update_pages(&mut self, page_no: usize, content: Vec<u8>) {
+        let new_item = PagesCacheItem::new(page_no, content);
+
+        let mut found_pages = Vec::new();
+        let mut insert_at = self.pages.len();
+
+        for (index, page) in self.pages.iter().enumerate() {
+            let result = page.is_my_page_to_merge(&new_item);
+
+            if result.is_before() {
+                insert_at = index;
+                break;
+            }
+
+            if result.is_here() {
+                found_pages.push(index);
+            }
+        }
+
+        if found_pages.is_empty() {
+            self.pages.insert(insert_at, new_item);
+            return;
+        }
+
+        let pages_to_merge: Vec<&PagesCacheItem> = found_pages
+            .iter()
+            .map(|index| &self.pages[*index])
+            .collect();
+
+        let merged_page = my_azure_page_blob_ext::merge_pages(pages_to_merge.as_slice(), &new_item);
+
+        let start_index = found_pages[0];
+        self.pages
+            .drain(start_index..start_index + found_pages.len());
+        self.pages.insert(start_index, merged_page);
+    }
+
+    fn get_page(&self, page_id: usize) -> Option<&[u8]> {
+        self.pages.iter().find_map(|page| page.get_content(page_id))
+    }
+}
+
+/// Scattered single page writes with gaps, so none of them are merged
+fn get_scattered_pages(intervals_amount: usize) -> Vec<usize> {
+    let mut state = 0x2545F4914F6CDD1Du64;
+    let mut result: Vec<usize> = (0..intervals_amount).map(|no| no * 2).collect();
+
+    for i in (1..result.len()).rev() {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        let j = (state.wrapping_mul(0x2545F4914F6CDD1D) % (i as u64 + 1)) as usize;
+        result.swap(i, j);
+    }
+
+    result
+}
+
+fn bench_update_pages(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_pages_scattered");
+
+    for intervals_amount in [100, 1_000, 5_000] {
+        let pages = get_scattered_pages(intervals_amount);
+
+        group.bench_with_input(
+            BenchmarkId::new("btree_map", intervals_amount),
+            &pages,
+            |b, pages| {
+                b.iter(|| {
+                    let mut intervals = PagesCacheIntervals::new();
+                    for page_no in pages {
+                        intervals.update_pages(*page_no, vec![0u8; PAGE_SIZE]);
+                    }
+                    black_box(intervals.get_intervals_amount())
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("linear", intervals_amount),
+            &pages,
+            |b, pages| {
+                b.iter(|| {
+                    let mut intervals = LinearPagesCacheIntervals::new();
+                    for page_no in pages {
+                        intervals.update_pages(*page_no, vec![0u8; PAGE_SIZE]);
+                    }
+                    black_box(intervals.pages.len())
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_get_page(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_page");
+
+    for intervals_amount in [100, 1_000, 5_000] {
+        let pages = get_scattered_pages(intervals_amount);
+
+        let mut btree_intervals = PagesCacheIntervals::new();
+        let mut linear_intervals = LinearPagesCacheIntervals::new();
+
+        for page_no in &pages {
+            btree_intervals.update_pages(*page_no, vec![0u8; PAGE_SIZE]);
+            linear_intervals.update_pages(*page_no, vec![0u8; PAGE_SIZE]);
+        }
+
+        group.bench_with_input(
+            BenchmarkId::new("btree_map", intervals_amount),
+            &pages,
+            |b, pages| {
+                b.iter(|| {
+                    for page_no in pages {
+                        black_box(btree_intervals.get_page(*page_no));
+                    }
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("linear", intervals_amount),
+            &pages,
+            |b, pages| {
+                b.iter(|| {
+                    for page_no in pages {
+                        black_box(linear_intervals.get_page(*page_no));
+                    }
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_update_pages, bench_get_page);
+criterion_main!(benches);
@@ -0,0 +1,239 @@
+use std::time::{Duration, Instant};
+
+use crate::pseudo_random::PseudoRandom;
+
+#[derive(Debug, Clone, Copy)]
+pub enum BackoffJitter {
+    None,
+    /// Random delay in range [0..calculated delay]
+    Full,
+    /// Random delay in range [initial delay..previous delay * 3]
+    Decorrelated,
+}
+
+#[derive(Debug, Clone)]
+pub enum BackoffStrategy {
+    Fixed(Duration),
+    Linear {
+        initial: Duration,
+        step: Duration,
+    },
+    Exponential {
+        initial: Duration,
+        multiplier: u32,
+        jitter: BackoffJitter,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct BackoffPolicy {
+    pub strategy: BackoffStrategy,
+    pub max_delay: Option<Duration>,
+    /// Total time budget for all the attempts of the operation
+    pub deadline: Option<Duration>,
+}
+
+impl BackoffPolicy {
+    pub fn fixed(delay: Duration) -> Self {
+        Self::new(BackoffStrategy::Fixed(delay))
+    }
+
+    pub fn linear(initial: Duration, step: Duration) -> Self {
+        Self::new(BackoffStrategy::Linear { initial, step })
+    }
+
+    pub fn exponential(initial: Duration, jitter: BackoffJitter) -> Self {
+        Self::new(BackoffStrategy::Exponential {
+            initial,
+            multiplier: 2,
+            jitter,
+        })
+    }
+
+    pub fn new(strategy: BackoffStrategy) -> Self {
+        Self {
+            strategy,
+            max_delay: None,
+            deadline: None,
+        }
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn start(&self) -> BackoffState {
+        BackoffState {
+            policy: self,
+            attempt_no: 0,
+            prev_delay: Duration::ZERO,
+            started: Instant::now(),
+            random: PseudoRandom::from_time(),
+        }
+    }
+
+    /// attempt_no starts from 1
+    fn calc_delay(
+        &self,
+        attempt_no: usize,
+        prev_delay: Duration,
+        random: &mut PseudoRandom,
+    ) -> Duration {
+        let delay = match &self.strategy {
+            BackoffStrategy::Fixed(delay) => *delay,
+            BackoffStrategy::Linear { initial, step } => {
+                initial.saturating_add(step.saturating_mul((attempt_no - 1) as u32))
+            }
+            BackoffStrategy::Exponential {
+                initial,
+                multiplier,
+                jitter,
+            } => {
+                let exponent = (attempt_no - 1).min(u32::MAX as usize) as u32;
+                let delay = initial.saturating_mul(multiplier.saturating_pow(exponent));
+                let delay = self.cap(delay);
+
+                match jitter {
+                    BackoffJitter::None => delay,
+                    BackoffJitter::Full => delay.mul_f64(random.next_f64()),
+                    BackoffJitter::Decorrelated => {
+                        let upper = prev_delay.saturating_mul(3).max(*initial);
+                        *initial + (upper - *initial).mul_f64(random.next_f64())
+                    }
+                }
+            }
+        };
+
+        self.cap(delay)
+    }
+
+    fn cap(&self, delay: Duration) -> Duration {
+        match self.max_delay {
+            Some(max_delay) => delay.min(max_delay),
+            None => delay,
+        }
+    }
+}
+
+pub struct BackoffState<'s> {
+    policy: &'s BackoffPolicy,
+    attempt_no: usize,
+    prev_delay: Duration,
+    started: Instant,
+    random: PseudoRandom,
+}
+
+impl<'s> BackoffState<'s> {
+    pub fn get_attempt_no(&self) -> usize {
+        self.attempt_no
+    }
+
+    /// Registers one more attempt. Returns None if the delay does not fit into the deadline
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        self.attempt_no += 1;
+
+        let delay = self
+            .policy
+            .calc_delay(self.attempt_no, self.prev_delay, &mut self.random);
+
+        if let Some(deadline) = self.policy.deadline {
+            if self.started.elapsed() + delay > deadline {
+                return None;
+            }
+        }
+
+        self.prev_delay = delay;
+        Some(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn get_delays(policy: &BackoffPolicy, amount: usize) -> Vec<Duration> {
+        let mut state = policy.start();
+        (0..amount).map(|_| state.next_delay().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_fixed_delay() {
+        let policy = BackoffPolicy::fixed(Duration::from_millis(100));
+
+        for delay in get_delays(&policy, 5) {
+            assert_eq!(Duration::from_millis(100), delay);
+        }
+    }
+
+    #[test]
+    fn test_linear_delay() {
+        let policy = BackoffPolicy::linear(Duration::from_millis(100), Duration::from_millis(50));
+
+        assert_eq!(
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(150),
+                Duration::from_millis(200),
+            ],
+            get_delays(&policy, 3)
+        );
+    }
+
+    #[test]
+    fn test_exponential_delay_is_capped() {
+        let policy = BackoffPolicy::exponential(Duration::from_millis(100), BackoffJitter::None)
+            .with_max_delay(Duration::from_millis(500));
+
+        assert_eq!(
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(400),
+                Duration::from_millis(500),
+                Duration::from_millis(500),
+            ],
+            get_delays(&policy, 5)
+        );
+    }
+
+    #[test]
+    fn test_full_jitter_does_not_exceed_calculated_delay() {
+        let policy = BackoffPolicy::exponential(Duration::from_millis(100), BackoffJitter::Full)
+            .with_max_delay(Duration::from_secs(1));
+
+        for (no, delay) in get_delays(&policy, 10).into_iter().enumerate() {
+            let max = Duration::from_millis(100 * (1 << no)).min(Duration::from_secs(1));
+            assert!(delay <= max);
+        }
+    }
+
+    #[test]
+    fn test_decorrelated_jitter_stays_in_bounds() {
+        let policy =
+            BackoffPolicy::exponential(Duration::from_millis(100), BackoffJitter::Decorrelated)
+                .with_max_delay(Duration::from_secs(2));
+
+        for delay in get_delays(&policy, 20) {
+            assert!(delay >= Duration::from_millis(100));
+            assert!(delay <= Duration::from_secs(2));
+        }
+    }
+
+    #[test]
+    fn test_deadline_stops_retries() {
+        let policy = BackoffPolicy::fixed(Duration::from_millis(100))
+            .with_deadline(Duration::from_millis(50));
+
+        let mut state = policy.start();
+
+        assert!(state.next_delay().is_none());
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use my_azure_storage_sdk::{
+    page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage, PageBlobProperties},
+    AzureStorageError,
+};
+use rust_extensions::SliceOrVec;
+
+use crate::{pseudo_random::PseudoRandom, PageBlobOperation};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InjectedError {
+    Timeout,
+    Throttling,
+}
+
+impl InjectedError {
+    pub fn to_azure_storage_error(&self) -> AzureStorageError {
+        match self {
+            InjectedError::Timeout => AzureStorageError::Timeout,
+            InjectedError::Throttling => AzureStorageError::UnknownError {
+                msg: "503 ServerBusy: injected throttling".to_string(),
+            },
+        }
+    }
+}
+
+/// Wraps page blob and injects failures into its calls. Same seed and same order of calls give the same failures.
+pub struct FaultInjectingPageBlob<
+    TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static,
+> {
+    page_blob: TMyAzurePageBlobStorage,
+    random: Mutex<PseudoRandom>,
+    calls: AtomicUsize,
+    injected_faults: AtomicUsize,
+    fail_calls: Vec<usize>,
+    failure_rate: f64,
+    latency: Option<Duration>,
+    injected_error: InjectedError,
+    torn_write_pages: Option<usize>,
+    operations: Option<Vec<PageBlobOperation>>,
+}
+
+impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static>
+    FaultInjectingPageBlob<TMyAzurePageBlobStorage>
+{
+    pub fn new(page_blob: TMyAzurePageBlobStorage, seed: u64) -> Self {
+        Self {
+            page_blob,
+            random: Mutex::new(PseudoRandom::new(seed)),
+            calls: AtomicUsize::new(0),
+            injected_faults: AtomicUsize::new(0),
+            fail_calls: Vec::new(),
+            failure_rate: 0.0,
+            latency: None,
+            injected_error: InjectedError::Timeout,
+            torn_write_pages: None,
+            operations: None,
+        }
+    }
+
+    /// Fails call with the number call_no. Calls are numbered from 1
+    pub fn fail_nth_call(mut self, call_no: usize) -> Self {
+        self.fail_calls.push(call_no);
+        self
+    }
+
+    /// Fails given percent [0..100] of calls
+    pub fn fail_percentage(mut self, percent: f64) -> Self {
+        self.failure_rate = percent.clamp(0.0, 100.0) / 100.0;
+        self
+    }
+
+    /// Delays every call, or only the calls selected by only_for
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    pub fn with_injected_error(mut self, injected_error: InjectedError) -> Self {
+        self.injected_error = injected_error;
+        self
+    }
+
+    /// Failed save_pages persists first pages_amount pages before returning the error
+    pub fn tear_writes(mut self, pages_amount: usize) -> Self {
+        self.torn_write_pages = Some(pages_amount);
+        self
+    }
+
+    /// Injects faults and latency only into given operations. Other operations are not counted as calls
+    pub fn only_for(mut self, operations: Vec<PageBlobOperation>) -> Self {
+        self.operations = Some(operations);
+        self
+    }
+
+    pub fn get_inner(&self) -> &TMyAzurePageBlobStorage {
+        &self.page_blob
+    }
+
+    pub fn get_calls_amount(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    pub fn get_injected_faults_amount(&self) -> usize {
+        self.injected_faults.load(Ordering::SeqCst)
+    }
+
+    fn is_targeted(&self, operation: PageBlobOperation) -> bool {
+        match &self.operations {
+            Some(operations) => operations.contains(&operation),
+            None => true,
+        }
+    }
+
+    fn should_fail(&self) -> bool {
+        let call_no = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let random_value = self.random.lock().unwrap().next_f64();
+
+        let result = self.fail_calls.contains(&call_no) || random_value < self.failure_rate;
+
+        if result {
+            self.injected_faults.fetch_add(1, Ordering::SeqCst);
+        }
+
+        result
+    }
+
+    /// Returns true if the fault has to be injected into the call
+    async fn before_call(&self, operation: PageBlobOperation) -> bool {
+        if !self.is_targeted(operation) {
+            return false;
+        }
+
+        if let Some(latency) = self.latency {
+            tokio::time::sleep(latency).await;
+        }
+
+        self.should_fail()
+    }
+
+    async fn inject(&self, operation: PageBlobOperation) -> Result<(), AzureStorageError> {
+        if self.before_call(operation).await {
+            return Err(self.injected_error.to_azure_storage_error());
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static> MyAzurePageBlobStorage
+    for FaultInjectingPageBlob<TMyAzurePageBlobStorage>
+{
+    fn get_blob_name(&self) -> &str {
+        self.page_blob.get_blob_name()
+    }
+
+    fn get_container_name(&self) -> &str {
+        self.page_blob.get_container_name()
+    }
+
+    async fn resize(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
+        self.inject(PageBlobOperation::Resize).await?;
+        self.page_blob.resize(pages_amount).await
+    }
+
+    async fn create_container_if_not_exists(&self) -> Result<(), AzureStorageError> {
+        self.inject(PageBlobOperation::CreateContainerIfNotExists)
+            .await?;
+        self.page_blob.create_container_if_not_exists().await
+    }
+
+    async fn create(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
+        self.inject(PageBlobOperation::Create).await?;
+        self.page_blob.create(pages_amount).await
+    }
+
+    async fn create_if_not_exists(
+        &self,
+        pages_amount: usize,
+        auto_create_container: bool,
+    ) -> Result<PageBlobProperties, AzureStorageError> {
+        self.inject(PageBlobOperation::CreateIfNotExists).await?;
+        self.page_blob
+            .create_if_not_exists(pages_amount, auto_create_container)
+            .await
+    }
+
+    async fn get_pages(
+        &self,
+        start_page_no: usize,
+        pages_amount: usize,
+    ) -> Result<Vec<u8>, AzureStorageError> {
+        self.inject(PageBlobOperation::GetPages).await?;
+        self.page_blob.get_pages(start_page_no, pages_amount).await
+    }
+
+    async fn save_pages<'s>(
+        &self,
+        start_page_no: usize,
+        payload: impl Into<SliceOrVec<'s, u8>> + Send + Sync + 'static,
+    ) -> Result<(), AzureStorageError> {
+        let payload: SliceOrVec<'s, u8> = payload.into();
+        let payload = payload.into_vec();
+
+        if !self.before_call(PageBlobOperation::SavePages).await {
+            return self.page_blob.save_pages(start_page_no, payload).await;
+        }
+
+        if let Some(torn_write_pages) = self.torn_write_pages {
+            let persisted_size = (torn_write_pages * BLOB_PAGE_SIZE).min(payload.len());
+
+            if persisted_size > 0 {
+                self.page_blob
+                    .save_pages(start_page_no, payload[..persisted_size].to_vec())
+                    .await?;
+            }
+        }
+
+        Err(self.injected_error.to_azure_storage_error())
+    }
+
+    async fn delete(&self) -> Result<(), AzureStorageError> {
+        self.inject(PageBlobOperation::Delete).await?;
+        self.page_blob.delete().await
+    }
+
+    async fn download(&self) -> Result<Vec<u8>, AzureStorageError> {
+        self.inject(PageBlobOperation::Download).await?;
+        self.page_blob.download().await
+    }
+
+    async fn get_blob_properties(&self) -> Result<PageBlobProperties, AzureStorageError> {
+        self.inject(PageBlobOperation::GetBlobProperties).await?;
+        self.page_blob.get_blob_properties().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use my_azure_storage_sdk::page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage};
+
+    use super::*;
+    use crate::{InMemoryPageBlob, MyAzurePageBlobStorageWithRetries};
+
+    async fn create_page_blob(pages_amount: usize) -> InMemoryPageBlob {
+        let page_blob = InMemoryPageBlob::new("container", "blob");
+        page_blob
+            .create_if_not_exists(pages_amount, true)
+            .await
+            .unwrap();
+        page_blob
+    }
+
+    #[tokio::test]
+    async fn test_nth_call_fails() {
+        let page_blob = FaultInjectingPageBlob::new(create_page_blob(1).await, 0).fail_nth_call(2);
+
+        assert!(page_blob.get_pages(0, 1).await.is_ok());
+        assert!(page_blob.get_pages(0, 1).await.is_err());
+        assert!(page_blob.get_pages(0, 1).await.is_ok());
+
+        assert_eq!(3, page_blob.get_calls_amount());
+        assert_eq!(1, page_blob.get_injected_faults_amount());
+    }
+
+    #[tokio::test]
+    async fn test_same_seed_injects_same_faults() {
+        let mut results = Vec::new();
+
+        for _ in 0..2 {
+            let page_blob =
+                FaultInjectingPageBlob::new(create_page_blob(1).await, 42).fail_percentage(50.0);
+
+            let mut calls = Vec::new();
+            for _ in 0..50 {
+                calls.push(page_blob.get_pages(0, 1).await.is_ok());
+            }
+
+            results.push(calls);
+        }
+
+        assert_eq!(results[0], results[1]);
+        assert!(results[0].iter().any(|ok| *ok));
+        assert!(results[0].iter().any(|ok| !*ok));
+    }
+
+    #[tokio::test]
+    async fn test_torn_write_persists_prefix() {
+        let page_blob = FaultInjectingPageBlob::new(create_page_blob(3).await, 0)
+            .fail_nth_call(1)
+            .tear_writes(1)
+            .with_injected_error(InjectedError::Throttling);
+
+        let result = page_blob.save_pages(0, vec![1u8; BLOB_PAGE_SIZE * 3]).await;
+        assert!(result.is_err());
+
+        let content = page_blob.get_inner().download().await.unwrap();
+
+        let mut expected = vec![1u8; BLOB_PAGE_SIZE];
+        expected.extend_from_slice([0u8; BLOB_PAGE_SIZE * 2].as_slice());
+
+        assert_eq!(expected, content);
+    }
+
+    #[tokio::test]
+    async fn test_retry_wrapper_survives_injected_faults() {
+        let page_blob = FaultInjectingPageBlob::new(create_page_blob(1).await, 0)
+            .fail_nth_call(1)
+            .fail_nth_call(2);
+
+        let page_blob = MyAzurePageBlobStorageWithRetries::new(page_blob, 2, Duration::ZERO);
+
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        assert_eq!(3, page_blob.page_blob.get_calls_amount());
+        assert_eq!(
+            vec![1u8; BLOB_PAGE_SIZE],
+            page_blob.page_blob.get_inner().download().await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_selected_operations_are_affected() {
+        let page_blob = FaultInjectingPageBlob::new(create_page_blob(1).await, 0)
+            .fail_percentage(100.0)
+            .only_for(vec![PageBlobOperation::SavePages]);
+
+        assert!(page_blob.get_pages(0, 1).await.is_ok());
+        assert!(page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_latency_is_applied_only_to_selected_operations() {
+        let page_blob = FaultInjectingPageBlob::new(create_page_blob(1).await, 0)
+            .with_latency(Duration::from_secs(10))
+            .only_for(vec![PageBlobOperation::SavePages]);
+
+        tokio::time::timeout(Duration::from_millis(100), page_blob.get_pages(0, 1))
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
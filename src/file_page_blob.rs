@@ -0,0 +1,392 @@
+use std::{
+    io::SeekFrom,
+    path::{Component, Path, PathBuf},
+};
+
+use my_azure_storage_sdk::{
+    blob::BlobProperties,
+    page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage, PageBlobProperties},
+    AzureStorageError,
+};
+use rust_extensions::SliceOrVec;
+use tokio::{
+    fs::OpenOptions,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+
+/// MyAzurePageBlobStorage implementation on top of local files for offline development.
+/// Container is a directory under the root directory, blob is a sparse file inside of it.
+/// Names which would lead outside of the root directory (`..`, absolute paths) are rejected
+/// with AzureStorageError::InvalidResourceName by every operation.
+pub struct FilePageBlob {
+    container_path: PathBuf,
+    blob_path: PathBuf,
+    container_name: String,
+    blob_name: String,
+    names_are_valid: bool,
+}
+
+impl FilePageBlob {
+    pub fn new(
+        root_path: impl AsRef<Path>,
+        container_name: impl Into<String>,
+        blob_name: impl Into<String>,
+    ) -> Self {
+        let container_name = container_name.into();
+        let blob_name = blob_name.into();
+
+        let container_path = root_path.as_ref().join(container_name.as_str());
+        let blob_path = container_path.join(blob_name.as_str());
+
+        // Container is a single directory, blob can be nested into virtual directories
+        let names_are_valid = is_valid_name(container_name.as_str(), false)
+            && is_valid_name(blob_name.as_str(), true);
+
+        Self {
+            container_path,
+            blob_path,
+            container_name,
+            blob_name,
+            names_are_valid,
+        }
+    }
+
+    pub fn get_blob_path(&self) -> &Path {
+        self.blob_path.as_path()
+    }
+
+    fn check_names(&self) -> Result<(), AzureStorageError> {
+        if self.names_are_valid {
+            Ok(())
+        } else {
+            Err(AzureStorageError::InvalidResourceName)
+        }
+    }
+
+    async fn check_container(&self) -> Result<(), AzureStorageError> {
+        self.check_names()?;
+
+        match tokio::fs::metadata(&self.container_path).await {
+            Ok(metadata) if metadata.is_dir() => Ok(()),
+            _ => Err(AzureStorageError::ContainerNotFound),
+        }
+    }
+
+    async fn get_blob_size(&self) -> Result<usize, AzureStorageError> {
+        self.check_container().await?;
+
+        let metadata = tokio::fs::metadata(&self.blob_path)
+            .await
+            .map_err(to_azure_storage_error)?;
+
+        Ok(metadata.len() as usize)
+    }
+
+    async fn create_blob_file(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
+        if let Some(parent) = self.blob_path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(to_azure_storage_error)?;
+        }
+
+        let file = tokio::fs::File::create(&self.blob_path)
+            .await
+            .map_err(to_azure_storage_error)?;
+
+        file.set_len((pages_amount * BLOB_PAGE_SIZE) as u64)
+            .await
+            .map_err(to_azure_storage_error)
+    }
+}
+
+/// Name has to consist of plain path components only, so the path stays under the root directory
+fn is_valid_name(name: &str, allow_nested: bool) -> bool {
+    let mut components_amount = 0;
+
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(_) => components_amount += 1,
+            _ => return false,
+        }
+    }
+
+    match components_amount {
+        0 => false,
+        1 => true,
+        _ => allow_nested,
+    }
+}
+
+fn to_azure_storage_error(err: std::io::Error) -> AzureStorageError {
+    match err.kind() {
+        std::io::ErrorKind::NotFound => AzureStorageError::BlobNotFound,
+        _ => AzureStorageError::UnknownError {
+            msg: format!("{:?}", err),
+        },
+    }
+}
+
+fn check_range(
+    blob_size: usize,
+    start_page_no: usize,
+    size: usize,
+) -> Result<u64, AzureStorageError> {
+    let offset = start_page_no * BLOB_PAGE_SIZE;
+
+    if size % BLOB_PAGE_SIZE != 0 || offset + size > blob_size {
+        return Err(AzureStorageError::InvalidPageRange);
+    }
+
+    Ok(offset as u64)
+}
+
+#[async_trait::async_trait]
+impl MyAzurePageBlobStorage for FilePageBlob {
+    fn get_blob_name(&self) -> &str {
+        self.blob_name.as_str()
+    }
+
+    fn get_container_name(&self) -> &str {
+        self.container_name.as_str()
+    }
+
+    async fn resize(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
+        self.get_blob_size().await?;
+
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&self.blob_path)
+            .await
+            .map_err(to_azure_storage_error)?;
+
+        file.set_len((pages_amount * BLOB_PAGE_SIZE) as u64)
+            .await
+            .map_err(to_azure_storage_error)
+    }
+
+    async fn create_container_if_not_exists(&self) -> Result<(), AzureStorageError> {
+        self.check_names()?;
+
+        tokio::fs::create_dir_all(&self.container_path)
+            .await
+            .map_err(to_azure_storage_error)
+    }
+
+    async fn create(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
+        self.check_container().await?;
+        self.create_blob_file(pages_amount).await
+    }
+
+    async fn create_if_not_exists(
+        &self,
+        pages_amount: usize,
+        auto_create_container: bool,
+    ) -> Result<PageBlobProperties, AzureStorageError> {
+        if auto_create_container {
+            self.create_container_if_not_exists().await?;
+        }
+
+        match self.get_blob_size().await {
+            Ok(_) => {}
+            Err(AzureStorageError::BlobNotFound) => {
+                self.create_blob_file(pages_amount).await?;
+            }
+            Err(err) => return Err(err),
+        }
+
+        self.get_blob_properties().await
+    }
+
+    async fn get_pages(
+        &self,
+        start_page_no: usize,
+        pages_amount: usize,
+    ) -> Result<Vec<u8>, AzureStorageError> {
+        let size = pages_amount * BLOB_PAGE_SIZE;
+        let offset = check_range(self.get_blob_size().await?, start_page_no, size)?;
+
+        let mut file = tokio::fs::File::open(&self.blob_path)
+            .await
+            .map_err(to_azure_storage_error)?;
+
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(to_azure_storage_error)?;
+
+        let mut result = vec![0u8; size];
+
+        file.read_exact(result.as_mut_slice())
+            .await
+            .map_err(to_azure_storage_error)?;
+
+        Ok(result)
+    }
+
+    async fn save_pages<'s>(
+        &self,
+        start_page_no: usize,
+        payload: impl Into<SliceOrVec<'s, u8>> + Send + Sync + 'static,
+    ) -> Result<(), AzureStorageError> {
+        let payload: SliceOrVec<'s, u8> = payload.into();
+        let payload = payload.as_slice();
+
+        let offset = check_range(self.get_blob_size().await?, start_page_no, payload.len())?;
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(&self.blob_path)
+            .await
+            .map_err(to_azure_storage_error)?;
+
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(to_azure_storage_error)?;
+
+        file.write_all(payload)
+            .await
+            .map_err(to_azure_storage_error)?;
+
+        file.flush().await.map_err(to_azure_storage_error)
+    }
+
+    async fn delete(&self) -> Result<(), AzureStorageError> {
+        self.check_container().await?;
+
+        tokio::fs::remove_file(&self.blob_path)
+            .await
+            .map_err(to_azure_storage_error)
+    }
+
+    async fn download(&self) -> Result<Vec<u8>, AzureStorageError> {
+        self.check_container().await?;
+
+        tokio::fs::read(&self.blob_path)
+            .await
+            .map_err(to_azure_storage_error)
+    }
+
+    async fn get_blob_properties(&self) -> Result<PageBlobProperties, AzureStorageError> {
+        let blob_size = self.get_blob_size().await?;
+        Ok(PageBlobProperties::new(BlobProperties { blob_size }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use my_azure_storage_sdk::{
+        page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage},
+        AzureStorageError,
+    };
+
+    use super::FilePageBlob;
+
+    static TEST_NO: AtomicUsize = AtomicUsize::new(0);
+
+    fn get_root_path() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "file-page-blob-test-{}-{}",
+            std::process::id(),
+            TEST_NO.fetch_add(1, Ordering::SeqCst)
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_save_get_and_resize() {
+        let root_path = get_root_path();
+        let page_blob = FilePageBlob::new(&root_path, "container", "blob");
+
+        page_blob.create_if_not_exists(2, true).await.unwrap();
+
+        page_blob
+            .save_pages(1, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        let mut expected = vec![0u8; BLOB_PAGE_SIZE];
+        expected.extend_from_slice([1u8; BLOB_PAGE_SIZE].as_slice());
+
+        assert_eq!(expected, page_blob.get_pages(0, 2).await.unwrap());
+
+        page_blob.resize(3).await.unwrap();
+
+        let properties = page_blob.get_blob_properties().await.unwrap();
+        assert_eq!(BLOB_PAGE_SIZE * 3, properties.blob_properties.blob_size);
+
+        page_blob.resize(1).await.unwrap();
+        assert_eq!(
+            vec![0u8; BLOB_PAGE_SIZE],
+            page_blob.download().await.unwrap()
+        );
+
+        tokio::fs::remove_dir_all(root_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_azure_errors() {
+        let root_path = get_root_path();
+        let page_blob = FilePageBlob::new(&root_path, "container", "blob");
+
+        let result = page_blob.create(1).await;
+        assert!(matches!(result, Err(AzureStorageError::ContainerNotFound)));
+
+        page_blob.create_container_if_not_exists().await.unwrap();
+
+        let result = page_blob.get_pages(0, 1).await;
+        assert!(matches!(result, Err(AzureStorageError::BlobNotFound)));
+
+        page_blob.create(1).await.unwrap();
+
+        let result = page_blob.save_pages(1, vec![1u8; BLOB_PAGE_SIZE]).await;
+        assert!(matches!(result, Err(AzureStorageError::InvalidPageRange)));
+
+        let result = page_blob.save_pages(0, vec![1u8; 10]).await;
+        assert!(matches!(result, Err(AzureStorageError::InvalidPageRange)));
+
+        page_blob.delete().await.unwrap();
+
+        let result = page_blob.delete().await;
+        assert!(matches!(result, Err(AzureStorageError::BlobNotFound)));
+
+        tokio::fs::remove_dir_all(root_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_names_leading_outside_of_root_are_rejected() {
+        let root_path = get_root_path();
+
+        for (container_name, blob_name) in [
+            ("..", "blob"),
+            ("container", "../blob"),
+            ("container", "dir/../../blob"),
+            ("container", "/tmp/blob"),
+            ("a/b", "blob"),
+            ("container", ""),
+        ] {
+            let page_blob = FilePageBlob::new(&root_path, container_name, blob_name);
+
+            let result = page_blob.create_if_not_exists(1, true).await;
+            assert!(matches!(
+                result,
+                Err(AzureStorageError::InvalidResourceName)
+            ));
+
+            let result = page_blob.get_pages(0, 1).await;
+            assert!(matches!(
+                result,
+                Err(AzureStorageError::InvalidResourceName)
+            ));
+        }
+
+        assert!(tokio::fs::metadata(&root_path).await.is_err());
+
+        let page_blob = FilePageBlob::new(&root_path, "container", "dir/blob");
+        page_blob.create_if_not_exists(1, true).await.unwrap();
+
+        tokio::fs::remove_dir_all(root_path).await.unwrap();
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use my_azure_storage_sdk::{
+    blob::BlobProperties,
+    page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage, PageBlobProperties},
+    AzureStorageError,
+};
+use rust_extensions::SliceOrVec;
+
+/// In-memory storage account. Blobs which share the storage see the same containers.
+pub struct InMemoryBlobStorage {
+    containers: Mutex<HashMap<String, HashMap<String, Vec<u8>>>>,
+}
+
+impl InMemoryBlobStorage {
+    pub fn new() -> Self {
+        Self {
+            containers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn has_container(&self, container_name: &str) -> bool {
+        self.containers.lock().unwrap().contains_key(container_name)
+    }
+
+    pub fn get_blob_content(&self, container_name: &str, blob_name: &str) -> Option<Vec<u8>> {
+        let containers = self.containers.lock().unwrap();
+        containers.get(container_name)?.get(blob_name).cloned()
+    }
+}
+
+/// MyAzurePageBlobStorage implementation which keeps everything in memory and follows Azure page blob semantics.
+/// Made for tests.
+pub struct InMemoryPageBlob {
+    storage: Arc<InMemoryBlobStorage>,
+    container_name: String,
+    blob_name: String,
+}
+
+impl InMemoryPageBlob {
+    pub fn new(container_name: impl Into<String>, blob_name: impl Into<String>) -> Self {
+        Self::with_storage(
+            Arc::new(InMemoryBlobStorage::new()),
+            container_name,
+            blob_name,
+        )
+    }
+
+    pub fn with_storage(
+        storage: Arc<InMemoryBlobStorage>,
+        container_name: impl Into<String>,
+        blob_name: impl Into<String>,
+    ) -> Self {
+        Self {
+            storage,
+            container_name: container_name.into(),
+            blob_name: blob_name.into(),
+        }
+    }
+
+    pub fn get_storage(&self) -> Arc<InMemoryBlobStorage> {
+        self.storage.clone()
+    }
+
+    fn with_container<TResult>(
+        &self,
+        action: impl FnOnce(&mut HashMap<String, Vec<u8>>) -> Result<TResult, AzureStorageError>,
+    ) -> Result<TResult, AzureStorageError> {
+        let mut containers = self.storage.containers.lock().unwrap();
+
+        match containers.get_mut(&self.container_name) {
+            Some(container) => action(container),
+            None => Err(AzureStorageError::ContainerNotFound),
+        }
+    }
+
+    fn with_blob<TResult>(
+        &self,
+        action: impl FnOnce(&mut Vec<u8>) -> Result<TResult, AzureStorageError>,
+    ) -> Result<TResult, AzureStorageError> {
+        self.with_container(|container| match container.get_mut(&self.blob_name) {
+            Some(blob) => action(blob),
+            None => Err(AzureStorageError::BlobNotFound),
+        })
+    }
+}
+
+fn get_range(
+    blob_size: usize,
+    start_page_no: usize,
+    size: usize,
+) -> Result<std::ops::Range<usize>, AzureStorageError> {
+    let from = start_page_no * BLOB_PAGE_SIZE;
+    let to = from + size;
+
+    if size % BLOB_PAGE_SIZE != 0 || to > blob_size {
+        return Err(AzureStorageError::InvalidPageRange);
+    }
+
+    Ok(from..to)
+}
+
+fn to_properties(blob: &[u8]) -> PageBlobProperties {
+    PageBlobProperties::new(BlobProperties {
+        blob_size: blob.len(),
+    })
+}
+
+#[async_trait::async_trait]
+impl MyAzurePageBlobStorage for InMemoryPageBlob {
+    fn get_blob_name(&self) -> &str {
+        self.blob_name.as_str()
+    }
+
+    fn get_container_name(&self) -> &str {
+        self.container_name.as_str()
+    }
+
+    async fn resize(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
+        self.with_blob(|blob| {
+            blob.resize(pages_amount * BLOB_PAGE_SIZE, 0);
+            Ok(())
+        })
+    }
+
+    async fn create_container_if_not_exists(&self) -> Result<(), AzureStorageError> {
+        let mut containers = self.storage.containers.lock().unwrap();
+
+        if !containers.contains_key(&self.container_name) {
+            containers.insert(self.container_name.clone(), HashMap::new());
+        }
+
+        Ok(())
+    }
+
+    async fn create(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
+        self.with_container(|container| {
+            container.insert(
+                self.blob_name.clone(),
+                vec![0u8; pages_amount * BLOB_PAGE_SIZE],
+            );
+            Ok(())
+        })
+    }
+
+    async fn create_if_not_exists(
+        &self,
+        pages_amount: usize,
+        auto_create_container: bool,
+    ) -> Result<PageBlobProperties, AzureStorageError> {
+        if auto_create_container {
+            self.create_container_if_not_exists().await?;
+        }
+
+        self.with_container(|container| {
+            if !container.contains_key(&self.blob_name) {
+                container.insert(
+                    self.blob_name.clone(),
+                    vec![0u8; pages_amount * BLOB_PAGE_SIZE],
+                );
+            }
+
+            Ok(to_properties(container.get(&self.blob_name).unwrap()))
+        })
+    }
+
+    async fn get_pages(
+        &self,
+        start_page_no: usize,
+        pages_amount: usize,
+    ) -> Result<Vec<u8>, AzureStorageError> {
+        self.with_blob(|blob| {
+            let range = get_range(blob.len(), start_page_no, pages_amount * BLOB_PAGE_SIZE)?;
+            Ok(blob[range].to_vec())
+        })
+    }
+
+    async fn save_pages<'s>(
+        &self,
+        start_page_no: usize,
+        payload: impl Into<SliceOrVec<'s, u8>> + Send + Sync + 'static,
+    ) -> Result<(), AzureStorageError> {
+        let payload: SliceOrVec<'s, u8> = payload.into();
+        let payload = payload.as_slice();
+
+        self.with_blob(|blob| {
+            let range = get_range(blob.len(), start_page_no, payload.len())?;
+            blob[range].copy_from_slice(payload);
+            Ok(())
+        })
+    }
+
+    async fn delete(&self) -> Result<(), AzureStorageError> {
+        self.with_container(|container| match container.remove(&self.blob_name) {
+            Some(_) => Ok(()),
+            None => Err(AzureStorageError::BlobNotFound),
+        })
+    }
+
+    async fn download(&self) -> Result<Vec<u8>, AzureStorageError> {
+        self.with_blob(|blob| Ok(blob.clone()))
+    }
+
+    async fn get_blob_properties(&self) -> Result<PageBlobProperties, AzureStorageError> {
+        self.with_blob(|blob| Ok(to_properties(blob)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use my_azure_storage_sdk::{
+        page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage},
+        AzureStorageError,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_operations_without_container_fail() {
+        let page_blob = InMemoryPageBlob::new("container", "blob");
+
+        let result = page_blob.create(1).await;
+        assert!(matches!(result, Err(AzureStorageError::ContainerNotFound)));
+
+        let result = page_blob.get_pages(0, 1).await;
+        assert!(matches!(result, Err(AzureStorageError::ContainerNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_operations_without_blob_fail() {
+        let page_blob = InMemoryPageBlob::new("container", "blob");
+        page_blob.create_container_if_not_exists().await.unwrap();
+
+        let result = page_blob.get_blob_properties().await;
+        assert!(matches!(result, Err(AzureStorageError::BlobNotFound)));
+
+        let result = page_blob.delete().await;
+        assert!(matches!(result, Err(AzureStorageError::BlobNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_save_and_get_pages() {
+        let page_blob = InMemoryPageBlob::new("container", "blob");
+        page_blob.create_if_not_exists(4, true).await.unwrap();
+
+        page_blob
+            .save_pages(1, vec![1u8; BLOB_PAGE_SIZE * 2])
+            .await
+            .unwrap();
+
+        let result = page_blob.get_pages(0, 4).await.unwrap();
+
+        let mut expected = vec![0u8; BLOB_PAGE_SIZE];
+        expected.extend_from_slice([1u8; BLOB_PAGE_SIZE * 2].as_slice());
+        expected.extend_from_slice([0u8; BLOB_PAGE_SIZE].as_slice());
+
+        assert_eq!(expected, result);
+    }
+
+    #[tokio::test]
+    async fn test_not_aligned_and_out_of_range_writes_are_rejected() {
+        let page_blob = InMemoryPageBlob::new("container", "blob");
+        page_blob.create_if_not_exists(2, true).await.unwrap();
+
+        let result = page_blob.save_pages(0, vec![1u8; BLOB_PAGE_SIZE + 1]).await;
+        assert!(matches!(result, Err(AzureStorageError::InvalidPageRange)));
+
+        let result = page_blob.save_pages(1, vec![1u8; BLOB_PAGE_SIZE * 2]).await;
+        assert!(matches!(result, Err(AzureStorageError::InvalidPageRange)));
+
+        let result = page_blob.get_pages(2, 1).await;
+        assert!(matches!(result, Err(AzureStorageError::InvalidPageRange)));
+    }
+
+    #[tokio::test]
+    async fn test_resize_truncates_and_extends_with_zeros() {
+        let page_blob = InMemoryPageBlob::new("container", "blob");
+        page_blob.create_if_not_exists(2, true).await.unwrap();
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE * 2])
+            .await
+            .unwrap();
+
+        page_blob.resize(1).await.unwrap();
+        page_blob.resize(2).await.unwrap();
+
+        let mut expected = vec![1u8; BLOB_PAGE_SIZE];
+        expected.extend_from_slice([0u8; BLOB_PAGE_SIZE].as_slice());
+
+        assert_eq!(expected, page_blob.download().await.unwrap());
+
+        let properties = page_blob.get_blob_properties().await.unwrap();
+        assert_eq!(BLOB_PAGE_SIZE * 2, properties.blob_properties.blob_size);
+    }
+
+    #[tokio::test]
+    async fn test_blobs_share_storage() {
+        let storage = Arc::new(InMemoryBlobStorage::new());
+
+        let blob_a = InMemoryPageBlob::with_storage(storage.clone(), "container", "a");
+        let blob_b = InMemoryPageBlob::with_storage(storage.clone(), "container", "b");
+
+        blob_a.create_if_not_exists(1, true).await.unwrap();
+        blob_b.create(1).await.unwrap();
+
+        assert!(storage.get_blob_content("container", "a").is_some());
+        assert!(storage.get_blob_content("container", "b").is_some());
+    }
+}
@@ -1,11 +1,34 @@
+mod backoff_policy;
+mod fault_injecting_page_blob;
+mod file_page_blob;
+mod in_memory_page_blob;
 #[cfg(feature = "blob_with_cache")]
 mod my_azure_page_blob_with_cache;
 mod pages_cache_intervals;
 #[cfg(feature = "blob_with_cache")]
 pub mod pages_cache_list;
+mod pseudo_random;
 #[cfg(feature = "blob_with_cache")]
 pub use my_azure_page_blob_with_cache::*;
 mod my_azure_page_blob_with_retries;
+mod page_blob_byte_access;
+mod page_blob_stream;
+mod page_blob_timeouts;
+mod retry_classifier;
+mod retry_executor;
+mod single_flight_reads;
+mod telemetry;
 pub mod utils;
+pub use backoff_policy::*;
+pub use fault_injecting_page_blob::*;
+pub use file_page_blob::*;
+pub use in_memory_page_blob::*;
 pub use my_azure_page_blob_with_retries::*;
+pub use page_blob_byte_access::*;
+pub use page_blob_stream::*;
+pub use page_blob_timeouts::*;
 pub use pages_cache_intervals::*;
+pub use retry_classifier::*;
+pub use retry_executor::*;
+pub use single_flight_reads::*;
+pub use telemetry::*;
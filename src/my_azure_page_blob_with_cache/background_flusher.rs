@@ -0,0 +1,103 @@
+use std::{sync::Arc, time::Duration};
+
+use my_azure_storage_sdk::page_blob::MyAzurePageBlobStorage;
+use tokio::{sync::oneshot, task::JoinHandle};
+
+use crate::{FlushResult, MyAzurePageBlobWithCache};
+
+pub struct BackgroundFlusherSettings {
+    pub flush_interval: Duration,
+    /// Pending writes are flushed earlier if their size in bytes exceeds the threshold
+    pub dirty_size_threshold: Option<usize>,
+}
+
+impl BackgroundFlusherSettings {
+    pub fn new(flush_interval: Duration) -> Self {
+        Self {
+            flush_interval,
+            dirty_size_threshold: None,
+        }
+    }
+
+    pub fn with_dirty_size_threshold(mut self, dirty_size_threshold: usize) -> Self {
+        self.dirty_size_threshold = Some(dirty_size_threshold);
+        self
+    }
+}
+
+/// Dropping the handle stops the flusher as well. The final flush is still done, but its result is lost
+pub struct BackgroundFlusherHandle {
+    stop_sender: Option<oneshot::Sender<()>>,
+    join_handle: Option<JoinHandle<FlushResult>>,
+}
+
+impl BackgroundFlusherHandle {
+    /// Stops the flusher and returns the result of the final flush
+    pub async fn stop(mut self) -> Result<FlushResult, tokio::task::JoinError> {
+        self.send_stop();
+        self.join_handle.take().unwrap().await
+    }
+
+    fn send_stop(&mut self) {
+        if let Some(stop_sender) = self.stop_sender.take() {
+            let _ = stop_sender.send(());
+        }
+    }
+}
+
+impl Drop for BackgroundFlusherHandle {
+    fn drop(&mut self) {
+        self.send_stop();
+    }
+}
+
+pub(crate) fn start<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static>(
+    page_blob: Arc<MyAzurePageBlobWithCache<TMyAzurePageBlobStorage>>,
+    flush_interval: Duration,
+) -> BackgroundFlusherHandle {
+    let (stop_sender, stop_receiver) = oneshot::channel();
+
+    page_blob.flusher_started();
+    let running_flusher = RunningFlusher { page_blob };
+
+    let join_handle = tokio::spawn(flusher_loop(running_flusher, flush_interval, stop_receiver));
+
+    BackgroundFlusherHandle {
+        stop_sender: Some(stop_sender),
+        join_handle: Some(join_handle),
+    }
+}
+
+/// Keeps the flusher counted as running until its task is finished, aborted or panicked
+struct RunningFlusher<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static> {
+    page_blob: Arc<MyAzurePageBlobWithCache<TMyAzurePageBlobStorage>>,
+}
+
+impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static> Drop
+    for RunningFlusher<TMyAzurePageBlobStorage>
+{
+    fn drop(&mut self) {
+        self.page_blob.flusher_stopped();
+    }
+}
+
+async fn flusher_loop<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static>(
+    running_flusher: RunningFlusher<TMyAzurePageBlobStorage>,
+    flush_interval: Duration,
+    mut stop_receiver: oneshot::Receiver<()>,
+) -> FlushResult {
+    let page_blob = &running_flusher.page_blob;
+
+    loop {
+        tokio::select! {
+            _ = &mut stop_receiver => break,
+            _ = tokio::time::sleep(flush_interval) => {}
+            _ = page_blob.wait_until_dirty_size_exceeded() => {}
+        }
+
+        // Intervals which are failed to flush stay in cache and are retried on the next round
+        page_blob.flush().await;
+    }
+
+    page_blob.flush().await
+}
@@ -0,0 +1,128 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Snapshot of the cache statistics since the cache was created or since the last reset
+#[derive(Debug, Clone, Default)]
+pub struct CacheStats {
+    /// Amount of get_pages calls
+    pub reads: u64,
+    /// Amount of get_pages calls served without going to the page blob
+    pub hits: u64,
+    pub bytes_from_pending_writes: u64,
+    pub bytes_from_cached_pages: u64,
+    pub bytes_from_remote: u64,
+    /// Amount of get_pages requests to the page blob, including read-ahead
+    pub remote_fetches: u64,
+    pub evicted_pages: u64,
+    /// Size of pending writes at the moment of the snapshot. It is not reset
+    pub dirty_bytes: usize,
+}
+
+impl CacheStats {
+    pub fn get_hit_ratio(&self) -> f64 {
+        if self.reads == 0 {
+            return 0.0;
+        }
+
+        self.hits as f64 / self.reads as f64
+    }
+}
+
+#[derive(Default)]
+pub struct CacheStatsCounters {
+    reads: AtomicU64,
+    hits: AtomicU64,
+    bytes_from_pending_writes: AtomicU64,
+    bytes_from_cached_pages: AtomicU64,
+    bytes_from_remote: AtomicU64,
+    remote_fetches: AtomicU64,
+    evicted_pages: AtomicU64,
+}
+
+impl CacheStatsCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_read(&self, hit: bool) {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+
+        if hit {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn add_bytes_from_pending_writes(&self, bytes: usize) {
+        self.bytes_from_pending_writes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes_from_cached_pages(&self, bytes: usize) {
+        self.bytes_from_cached_pages
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_remote_fetch(&self, bytes: usize) {
+        self.remote_fetches.fetch_add(1, Ordering::Relaxed);
+        self.bytes_from_remote
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_evicted_pages(&self, evicted_pages: usize) {
+        if evicted_pages > 0 {
+            self.evicted_pages
+                .fetch_add(evicted_pages as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn get_snapshot(&self, dirty_bytes: usize, reset: bool) -> CacheStats {
+        let get = |counter: &AtomicU64| {
+            if reset {
+                counter.swap(0, Ordering::Relaxed)
+            } else {
+                counter.load(Ordering::Relaxed)
+            }
+        };
+
+        CacheStats {
+            reads: get(&self.reads),
+            hits: get(&self.hits),
+            bytes_from_pending_writes: get(&self.bytes_from_pending_writes),
+            bytes_from_cached_pages: get(&self.bytes_from_cached_pages),
+            bytes_from_remote: get(&self.bytes_from_remote),
+            remote_fetches: get(&self.remote_fetches),
+            evicted_pages: get(&self.evicted_pages),
+            dirty_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CacheStatsCounters;
+
+    #[test]
+    fn test_snapshot_and_reset() {
+        let counters = CacheStatsCounters::new();
+
+        counters.add_read(true);
+        counters.add_read(false);
+        counters.add_remote_fetch(1024);
+
+        let stats = counters.get_snapshot(512, false);
+
+        assert_eq!(2, stats.reads);
+        assert_eq!(1, stats.hits);
+        assert_eq!(0.5, stats.get_hit_ratio());
+        assert_eq!(1, stats.remote_fetches);
+        assert_eq!(1024, stats.bytes_from_remote);
+        assert_eq!(512, stats.dirty_bytes);
+
+        let stats = counters.get_snapshot(512, true);
+        assert_eq!(2, stats.reads);
+
+        let stats = counters.get_snapshot(512, false);
+        assert_eq!(0, stats.reads);
+        assert_eq!(0, stats.remote_fetches);
+        assert_eq!(512, stats.dirty_bytes);
+    }
+}
@@ -0,0 +1,16 @@
+/// What save_pages does once pending writes do not fit the cache size budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirtyDataBackpressure {
+    /// The write is accepted and pending writes are flushed before save_pages returns.
+    /// Intervals which failed to flush stay pending, save_pages still succeeds
+    FlushInline,
+    /// The write waits until the background flusher makes room for it.
+    /// While no background flusher is running, pending writes are flushed inline
+    WaitForFlusher,
+}
+
+impl Default for DirtyDataBackpressure {
+    fn default() -> Self {
+        Self::FlushInline
+    }
+}
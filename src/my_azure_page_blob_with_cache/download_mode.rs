@@ -0,0 +1,14 @@
+/// How MyAzurePageBlobWithCache::download deals with the pending writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadMode {
+    /// Pending writes are put on top of the downloaded content and stay pending
+    OverlayPendingWrites,
+    /// Pending writes are flushed before the download. Download fails if the flush fails
+    FlushFirst,
+}
+
+impl Default for DownloadMode {
+    fn default() -> Self {
+        Self::OverlayPendingWrites
+    }
+}
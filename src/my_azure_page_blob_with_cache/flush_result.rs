@@ -0,0 +1,78 @@
+use my_azure_storage_sdk::AzureStorageError;
+
+pub struct FlushedInterval {
+    pub from_page_no: usize,
+    pub amount: usize,
+}
+
+pub struct FailedToFlushInterval {
+    pub from_page_no: usize,
+    pub amount: usize,
+    pub err: AzureStorageError,
+}
+
+pub struct FlushResult {
+    pub flushed: Vec<FlushedInterval>,
+    pub failed: Vec<FailedToFlushInterval>,
+}
+
+impl FlushResult {
+    pub fn new() -> Self {
+        Self {
+            flushed: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    pub fn get_flushed_pages_amount(&self) -> usize {
+        self.flushed.iter().map(|itm| itm.amount).sum()
+    }
+
+    pub fn into_result(mut self) -> Result<(), AzureStorageError> {
+        if self.failed.is_empty() {
+            return Ok(());
+        }
+
+        Err(self.failed.remove(0).err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use my_azure_storage_sdk::AzureStorageError;
+
+    use super::*;
+
+    #[test]
+    fn test_empty_result_is_ok() {
+        let result = FlushResult::new();
+
+        assert!(result.is_ok());
+        assert_eq!(0, result.get_flushed_pages_amount());
+        assert!(result.into_result().is_ok());
+    }
+
+    #[test]
+    fn test_result_with_failed_interval_is_not_ok() {
+        let mut result = FlushResult::new();
+
+        result.flushed.push(FlushedInterval {
+            from_page_no: 0,
+            amount: 2,
+        });
+
+        result.failed.push(FailedToFlushInterval {
+            from_page_no: 5,
+            amount: 1,
+            err: AzureStorageError::InvalidPageRange,
+        });
+
+        assert!(!result.is_ok());
+        assert_eq!(2, result.get_flushed_pages_amount());
+        assert!(result.into_result().is_err());
+    }
+}
@@ -51,6 +51,11 @@ impl<'s> FoundPages<'s> {
             return;
         };
 
+        self.upload_missing_pages_from(download_start_page, payload);
+    }
+
+    /// Same as upload_missing_pages, but the payload is downloaded starting from the given page
+    pub fn upload_missing_pages_from(&mut self, download_start_page: usize, payload: &'s [u8]) {
         for missing_interval in &self.missing_intervals {
             for i in 0..missing_interval.amount {
                 let offset_pages = (missing_interval.from_page_no - download_start_page) + i;
@@ -1,7 +1,23 @@
+mod background_flusher;
+mod cache_stats;
+mod dirty_data_backpressure;
+mod download_mode;
+mod flush_result;
 mod found_pages;
 mod my_azure_page_blob_with_cache;
+mod my_azure_page_blob_with_cache_builder;
 mod page_blob_cached_data;
+mod pending_writes_on_shrink;
+mod read_ahead;
 
+pub use background_flusher::{BackgroundFlusherHandle, BackgroundFlusherSettings};
+pub use cache_stats::*;
+pub use dirty_data_backpressure::*;
+pub use download_mode::*;
+pub use flush_result::*;
 pub use found_pages::*;
 pub use my_azure_page_blob_with_cache::*;
+pub use my_azure_page_blob_with_cache_builder::*;
 pub use page_blob_cached_data::*;
+pub use pending_writes_on_shrink::*;
+pub use read_ahead::ReadAheadSettings;
@@ -1,27 +1,561 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use futures::StreamExt;
 use my_azure_storage_sdk::{
-    page_blob::{MyAzurePageBlobStorage, PageBlobProperties},
+    page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage, PageBlobProperties},
     AzureStorageError,
 };
+use my_telemetry::MyTelemetryContext;
 use rust_extensions::AsSliceOrVec;
-use tokio::sync::Mutex;
+use tokio::sync::{Notify, RwLock};
 
-use crate::{FoundPages, PageBlobCachedData};
+use super::read_ahead::ReadAheadState;
+use crate::{
+    utils::{split_into_pages_chunks, MAX_PUT_PAGES_SIZE},
+    BackgroundFlusherHandle, BackgroundFlusherSettings, CacheLookup, CacheStats,
+    CacheStatsCounters, DirtyDataBackpressure, DownloadMode, FailedToFlushInterval, FlushResult,
+    FlushedInterval, MyAzurePageBlobWithCacheBuilder, PageBlobCachedData, PageBlobOperation,
+    PageBlobTelemetryEvent, PagesCacheItem, PendingWritesOnShrink, ReadAheadSettings,
+    SingleFlightReads,
+};
 
 pub struct MyAzurePageBlobWithCache<
     TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static,
 > {
-    page_blob: TMyAzurePageBlobStorage,
-    cache: Mutex<PageBlobCachedData>,
+    page_blob: Arc<TMyAzurePageBlobStorage>,
+    cache: Arc<RwLock<PageBlobCachedData>>,
+    dirty_size_threshold: AtomicUsize,
+    dirty_size_exceeded: Notify,
+    flushed: Notify,
+    running_flushers: AtomicUsize,
+    flush_lock: tokio::sync::Mutex<()>,
+    flush_parallelism: usize,
+    telemetry: Option<MyTelemetryContext>,
+    pending_writes_on_shrink: PendingWritesOnShrink,
+    download_mode: DownloadMode,
+    warm_cache_on_download: bool,
+    single_flight_reads: Arc<SingleFlightReads>,
+    read_ahead: Option<Mutex<ReadAheadState>>,
+    dirty_data_backpressure: DirtyDataBackpressure,
+    stats: Arc<CacheStatsCounters>,
 }
 
 impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static>
     MyAzurePageBlobWithCache<TMyAzurePageBlobStorage>
 {
     pub fn new(page_blob: TMyAzurePageBlobStorage) -> Self {
+        Self::from_cached_data(page_blob, PageBlobCachedData::new())
+    }
+
+    pub fn builder(
+        page_blob: TMyAzurePageBlobStorage,
+    ) -> MyAzurePageBlobWithCacheBuilder<TMyAzurePageBlobStorage> {
+        MyAzurePageBlobWithCacheBuilder::new(page_blob)
+    }
+
+    pub(crate) fn from_cached_data(
+        page_blob: TMyAzurePageBlobStorage,
+        cached_data: PageBlobCachedData,
+    ) -> Self {
         Self {
-            page_blob,
-            cache: Mutex::new(PageBlobCachedData::new()),
+            stats: cached_data.stats.clone(),
+            page_blob: Arc::new(page_blob),
+            cache: Arc::new(RwLock::new(cached_data)),
+            dirty_size_threshold: AtomicUsize::new(0),
+            dirty_size_exceeded: Notify::new(),
+            flushed: Notify::new(),
+            running_flushers: AtomicUsize::new(0),
+            flush_lock: tokio::sync::Mutex::new(()),
+            flush_parallelism: 1,
+            telemetry: None,
+            pending_writes_on_shrink: PendingWritesOnShrink::default(),
+            download_mode: DownloadMode::default(),
+            warm_cache_on_download: false,
+            single_flight_reads: Arc::new(SingleFlightReads::new()),
+            read_ahead: None,
+            dirty_data_backpressure: DirtyDataBackpressure::default(),
+        }
+    }
+
+    pub fn with_telemetry(mut self, telemetry: MyTelemetryContext) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    async fn write_telemetry(
+        &self,
+        event: PageBlobTelemetryEvent,
+        err: Option<&AzureStorageError>,
+    ) {
+        crate::write_telemetry_event(
+            self.telemetry.as_ref(),
+            self.page_blob.get_container_name(),
+            self.page_blob.get_blob_name(),
+            event,
+            err,
+        )
+        .await;
+    }
+
+    pub fn with_pending_writes_on_shrink(
+        mut self,
+        pending_writes_on_shrink: PendingWritesOnShrink,
+    ) -> Self {
+        self.pending_writes_on_shrink = pending_writes_on_shrink;
+        self
+    }
+
+    /// Sets what save_pages does once pending writes exceed the cache size budget
+    pub fn with_dirty_data_backpressure(
+        mut self,
+        dirty_data_backpressure: DirtyDataBackpressure,
+    ) -> Self {
+        self.dirty_data_backpressure = dirty_data_backpressure;
+        self
+    }
+
+    pub fn with_download_mode(mut self, download_mode: DownloadMode) -> Self {
+        self.download_mode = download_mode;
+        self
+    }
+
+    /// Puts the result of download into the read cache
+    pub fn with_cache_warming_on_download(mut self, warm_cache_on_download: bool) -> Self {
+        self.warm_cache_on_download = warm_cache_on_download;
+        self
+    }
+
+    /// Prefetches pages in background once reads go one after another
+    pub fn with_read_ahead(mut self, settings: ReadAheadSettings) -> Self {
+        self.read_ahead = Some(Mutex::new(ReadAheadState::new(settings)));
+        self
+    }
+
+    fn start_read_ahead(&self, start_page_no: usize, pages_amount: usize) {
+        let Some(read_ahead) = &self.read_ahead else {
+            return;
+        };
+
+        let mut read_ahead = read_ahead.lock().unwrap();
+
+        let Some(interval) = read_ahead.register_read(start_page_no, pages_amount) else {
+            return;
+        };
+
+        let prefetch = tokio::spawn(super::read_ahead::prefetch(
+            self.page_blob.clone(),
+            self.cache.clone(),
+            self.single_flight_reads.clone(),
+            self.stats.clone(),
+            interval,
+        ));
+
+        read_ahead.set_prefetch(prefetch);
+    }
+
+    #[cfg(test)]
+    async fn wait_for_prefetch(&self) {
+        let prefetch = self
+            .read_ahead
+            .as_ref()
+            .and_then(|read_ahead| read_ahead.lock().unwrap().take_prefetch());
+
+        if let Some(prefetch) = prefetch {
+            prefetch.await.unwrap();
+        }
+    }
+
+    pub async fn get_cache_stats(&self) -> CacheStats {
+        let dirty_bytes = self.cache.read().await.pages_to_write.get_dirty_size();
+        self.stats.get_snapshot(dirty_bytes, false)
+    }
+
+    /// Returns statistics collected since the previous reset and starts collecting them from scratch
+    pub async fn get_and_reset_cache_stats(&self) -> CacheStats {
+        let dirty_bytes = self.cache.read().await.pages_to_write.get_dirty_size();
+        self.stats.get_snapshot(dirty_bytes, true)
+    }
+
+    /// Sets how many Put Page requests can be issued concurrently during the flush
+    pub fn with_flush_parallelism(mut self, flush_parallelism: usize) -> Self {
+        self.flush_parallelism = flush_parallelism.max(1);
+        self
+    }
+
+    /// Spawns a task which flushes pending writes periodically and once they exceed the dirty size threshold.
+    /// The final flush is done when the flusher is stopped.
+    pub fn start_background_flusher(
+        self: &Arc<Self>,
+        settings: BackgroundFlusherSettings,
+    ) -> BackgroundFlusherHandle {
+        self.dirty_size_threshold.store(
+            settings.dirty_size_threshold.unwrap_or(0),
+            Ordering::Relaxed,
+        );
+
+        super::background_flusher::start(self.clone(), settings.flush_interval)
+    }
+
+    pub(crate) fn flusher_started(&self) {
+        self.running_flushers.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn flusher_stopped(&self) {
+        self.running_flushers.fetch_sub(1, Ordering::SeqCst);
+        // Writers which wait for the flusher flush by themselves from now on
+        self.flushed.notify_waiters();
+    }
+
+    pub(crate) async fn wait_until_dirty_size_exceeded(&self) {
+        self.dirty_size_exceeded.notified().await
+    }
+
+    /// Pushes every pending write interval to the inner page blob.
+    /// Intervals are uploaded by chunks which fit into a single Put Page request.
+    /// Intervals which failed to upload are kept in the cache and reported in the result.
+    pub async fn flush(&self) -> FlushResult {
+        let event = PageBlobTelemetryEvent::new("flush");
+
+        let result = self.flush_pending_writes().await;
+        self.flushed.notify_waiters();
+
+        let event = event.with_bytes(result.get_flushed_pages_amount() * BLOB_PAGE_SIZE);
+        self.write_telemetry(event, result.failed.first().map(|failed| &failed.err))
+            .await;
+
+        result
+    }
+
+    async fn flush_pending_writes(&self) -> FlushResult {
+        // Flushes go one by one, so an upload of an older snapshot never lands after an upload of a newer one
+        let _flush_guard = self.flush_lock.lock().await;
+
+        // Readers keep seeing pending writes until they are uploaded, so intervals stay in the cache during the upload
+        let pending_writes: Vec<PagesCacheItem> = {
+            let read_access = self.cache.read().await;
+            read_access.pages_to_write.iter().cloned().collect()
+        };
+
+        let mut result = FlushResult::new();
+        let mut flushed_items = Vec::new();
+
+        for item in pending_writes {
+            let failed_chunks = self.upload_by_chunks(&item).await;
+
+            if failed_chunks.is_empty() {
+                result.flushed.push(FlushedInterval {
+                    from_page_no: item.page_id,
+                    amount: item.get_pages_amount(),
+                });
+                flushed_items.push(item);
+            } else {
+                result.failed.extend(failed_chunks);
+            }
+        }
+
+        if flushed_items.is_empty() {
+            return result;
+        }
+
+        let mut write_access = self.cache.write().await;
+
+        let mut removed = false;
+
+        // Intervals which were written during the upload stay pending and go with the next flush
+        for item in flushed_items {
+            removed |= write_access.pages_to_write.remove_if_unchanged(&item);
+        }
+
+        // Reads which looked up pending pages before the flush must not join or cache downloads started before it
+        if removed {
+            write_access.write_generation += 1;
+        }
+
+        result
+    }
+
+    /// Cache lookups are done under the read lock. Missing pages are downloaded without holding the lock
+    /// and are merged with pages which could have been written while the download was in progress.
+    async fn read_pages(
+        &self,
+        start_page_no: usize,
+        pages_amount: usize,
+        event: &mut PageBlobTelemetryEvent,
+    ) -> Result<Vec<u8>, AzureStorageError> {
+        loop {
+            let (pages_to_upload, write_generation) = {
+                let read_access = self.cache.read().await;
+
+                // Bytes are counted once per read, not on every round
+                let found_pages = if event.cache_lookup.is_none() {
+                    read_access.lookup_pages(start_page_no, pages_amount)
+                } else {
+                    read_access.find_pages(start_page_no, pages_amount)
+                };
+
+                let Some(pages_to_upload) = found_pages.get_pages_to_upload() else {
+                    if event.cache_lookup.is_none() {
+                        event.cache_lookup = Some(CacheLookup::Hit);
+                        self.stats.add_read(true);
+                    }
+                    return Ok(found_pages.into_vec());
+                };
+
+                if event.cache_lookup.is_none() {
+                    self.stats.add_read(false);
+
+                    if pages_to_upload.amount == pages_amount {
+                        event.cache_lookup = Some(CacheLookup::Miss);
+                    } else {
+                        event.cache_lookup = Some(CacheLookup::PartialHit);
+                    }
+                }
+
+                (pages_to_upload, read_access.write_generation)
+            };
+
+            let payload = self
+                .single_flight_reads
+                .get_pages(
+                    pages_to_upload.from_page_no,
+                    pages_to_upload.amount,
+                    write_generation,
+                    |from_page_no, pages_amount| async move {
+                        let payload = self.page_blob.get_pages(from_page_no, pages_amount).await?;
+                        self.stats.add_remote_fetch(payload.len());
+                        Ok::<_, AzureStorageError>(payload)
+                    },
+                )
+                .await?;
+
+            // Pages beyond the payload can not be downloaded by one more round either
+            if payload.len() < pages_to_upload.amount * BLOB_PAGE_SIZE {
+                return Err(AzureStorageError::InvalidPageRange);
+            }
+
+            let mut write_access = self.cache.write().await;
+
+            // Pages found during the lookup could be dropped meanwhile. Then we do one more round
+            let Some((result, downloaded_intervals)) = write_access.merge_downloaded_pages(
+                start_page_no,
+                pages_amount,
+                &pages_to_upload,
+                payload.as_slice(),
+            ) else {
+                continue;
+            };
+
+            // Downloaded content can be outdated if the blob was changed during the download
+            if write_access.write_generation == write_generation {
+                write_access.cache_downloaded_pages(
+                    pages_to_upload.from_page_no,
+                    downloaded_intervals.as_slice(),
+                    payload.as_slice(),
+                );
+            }
+
+            return Ok(result);
+        }
+    }
+
+    async fn upload_by_chunks(&self, item: &PagesCacheItem) -> Vec<FailedToFlushInterval> {
+        let page_blob = &self.page_blob;
+
+        let uploads = split_into_pages_chunks(
+            item.page_id,
+            item.content.as_slice(),
+            BLOB_PAGE_SIZE,
+            MAX_PUT_PAGES_SIZE,
+        )
+        .map(|chunk| {
+            let from_page_no = chunk.start_page_no;
+            let amount = chunk.get_pages_amount(BLOB_PAGE_SIZE);
+            let payload = chunk.payload.to_vec();
+
+            async move {
+                match page_blob.save_pages(from_page_no, payload).await {
+                    Ok(_) => None,
+                    Err(err) => Some(FailedToFlushInterval {
+                        from_page_no,
+                        amount,
+                        err,
+                    }),
+                }
+            }
+        });
+
+        futures::stream::iter(uploads)
+            .buffer_unordered(self.flush_parallelism)
+            .filter_map(|failed| async move { failed })
+            .collect()
+            .await
+    }
+
+    /// Runs the operation and writes its event to the telemetry
+    async fn execute_with_event<TResult>(
+        &self,
+        event: PageBlobTelemetryEvent,
+        action: impl Future<Output = Result<TResult, AzureStorageError>>,
+    ) -> Result<TResult, AzureStorageError> {
+        let result = action.await;
+        self.write_telemetry(event, result.as_ref().err()).await;
+        result
+    }
+
+    async fn resize_blob(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
+        // Flush in progress would upload pending writes which are dropped here
+        let _flush_guard = self.flush_lock.lock().await;
+        let mut write_access = self.cache.write().await;
+
+        if self.pending_writes_on_shrink == PendingWritesOnShrink::Reject
+            && write_access.pages_to_write.has_pages_from(pages_amount)
+        {
+            return Err(AzureStorageError::InvalidPageRange);
+        }
+
+        self.page_blob.resize(pages_amount).await?;
+        write_access.truncate(pages_amount);
+        Ok(())
+    }
+
+    async fn create_blob(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
+        let _flush_guard = self.flush_lock.lock().await;
+        let mut write_access = self.cache.write().await;
+        self.page_blob.create(pages_amount).await?;
+
+        // Create replaces the blob, so nothing we know about the old one is valid
+        write_access.clear();
+        write_access.update_pages_amount(pages_amount);
+        Ok(())
+    }
+
+    async fn create_blob_if_not_exists(
+        &self,
+        pages_amount: usize,
+        auto_create_container: bool,
+    ) -> Result<PageBlobProperties, AzureStorageError> {
+        let mut write_access = self.cache.write().await;
+
+        let page_blob_properties = self
+            .page_blob
+            .create_if_not_exists(pages_amount, auto_create_container)
+            .await?;
+
+        write_access.update_blob_properties(page_blob_properties.clone());
+        Ok(page_blob_properties)
+    }
+
+    async fn write_pages(
+        &self,
+        start_page_no: usize,
+        payload: Vec<u8>,
+    ) -> Result<(), AzureStorageError> {
+        loop {
+            // Subscribe before checking the flusher, so the flush or the flusher stop which happens meanwhile is not missed
+            let flushed = self.flushed.notified();
+            tokio::pin!(flushed);
+            flushed.as_mut().enable();
+
+            let mut write_access = self.cache.write().await;
+
+            let wait_for_flusher = self.dirty_data_backpressure
+                == DirtyDataBackpressure::WaitForFlusher
+                && self.running_flushers.load(Ordering::SeqCst) > 0;
+
+            if wait_for_flusher && write_access.is_dirty_size_over_budget(payload.len()) {
+                drop(write_access);
+
+                self.dirty_size_exceeded.notify_one();
+                flushed.await;
+                continue;
+            }
+
+            write_access.save_pages(start_page_no, payload);
+
+            let dirty_size_threshold = self.dirty_size_threshold.load(Ordering::Relaxed);
+
+            if dirty_size_threshold > 0
+                && write_access.pages_to_write.get_dirty_size() > dirty_size_threshold
+            {
+                self.dirty_size_exceeded.notify_one();
+            }
+
+            let flush_inline = !wait_for_flusher && write_access.is_dirty_size_over_budget(0);
+
+            drop(write_access);
+
+            // The write is accepted already. Intervals which failed to flush stay pending and the failure goes to telemetry
+            if flush_inline {
+                self.flush().await;
+            }
+
+            return Ok(());
+        }
+    }
+
+    async fn delete_blob(&self) -> Result<(), AzureStorageError> {
+        let _flush_guard = self.flush_lock.lock().await;
+        let mut write_access = self.cache.write().await;
+        self.page_blob.delete().await?;
+        write_access.clear();
+        Ok(())
+    }
+
+    async fn download_blob(&self) -> Result<Vec<u8>, AzureStorageError> {
+        if self.download_mode == DownloadMode::FlushFirst {
+            self.flush().await.into_result()?;
+        }
+
+        // Flushes and blob changes wait for the download, so pending writes stay pending until they are overlaid.
+        // The cache lock is not held during the download, so reads and writes are not blocked by it
+        let _flush_guard = self.flush_lock.lock().await;
+
+        let mut result = self.page_blob.download().await?;
+
+        let mut write_access = self.cache.write().await;
+
+        // Writes which came during the download are pending as well, so the result is up to date
+        write_access.overlay_pending_writes(result.as_mut_slice());
+        write_access.update_pages_amount(result.len() / BLOB_PAGE_SIZE);
+
+        if self.warm_cache_on_download {
+            write_access.warm_cache(0, result.as_slice());
         }
+
+        Ok(result)
+    }
+
+    async fn read_blob_properties(
+        &self,
+        event: &mut PageBlobTelemetryEvent,
+    ) -> Result<PageBlobProperties, AzureStorageError> {
+        let write_generation = {
+            let read_access = self.cache.read().await;
+            if let Some(blob_properties) = &read_access.page_blob_properties {
+                event.cache_lookup = Some(CacheLookup::Hit);
+                return Ok(blob_properties.clone());
+            }
+
+            read_access.write_generation
+        };
+
+        event.cache_lookup = Some(CacheLookup::Miss);
+
+        let page_blob_properties = self.page_blob.get_blob_properties().await?;
+
+        let mut write_access = self.cache.write().await;
+
+        if write_access.write_generation == write_generation {
+            write_access.update_blob_properties(page_blob_properties.clone());
+        }
+
+        Ok(page_blob_properties)
     }
 }
 
@@ -38,18 +572,34 @@ impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static> My
     }
 
     async fn create_container_if_not_exists(&self) -> Result<(), AzureStorageError> {
-        self.page_blob.create_container_if_not_exists().await
+        let event = PageBlobTelemetryEvent::new(PageBlobOperation::CreateContainerIfNotExists);
+        self.execute_with_event(event, self.page_blob.create_container_if_not_exists())
+            .await
     }
 
     async fn resize(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
-        self.page_blob.resize(pages_amount).await?;
-        let mut write_access = self.cache.lock().await;
-        write_access.update_pages_amount(pages_amount);
-        Ok(())
+        let event = PageBlobTelemetryEvent::new(PageBlobOperation::Resize);
+        self.execute_with_event(event, self.resize_blob(pages_amount))
+            .await
     }
 
     async fn create(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
-        self.page_blob.create(pages_amount).await
+        let event = PageBlobTelemetryEvent::new(PageBlobOperation::Create);
+        self.execute_with_event(event, self.create_blob(pages_amount))
+            .await
+    }
+
+    async fn create_if_not_exists(
+        &self,
+        pages_amount: usize,
+        auto_create_container: bool,
+    ) -> Result<PageBlobProperties, AzureStorageError> {
+        let event = PageBlobTelemetryEvent::new(PageBlobOperation::CreateIfNotExists);
+        self.execute_with_event(
+            event,
+            self.create_blob_if_not_exists(pages_amount, auto_create_container),
+        )
+        .await
     }
 
     async fn get_pages(
@@ -57,31 +607,18 @@ impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static> My
         start_page_no: usize,
         pages_amount: usize,
     ) -> Result<Vec<u8>, AzureStorageError> {
-        let write_access = self.cache.lock().await;
+        let mut event = PageBlobTelemetryEvent::new(PageBlobOperation::GetPages)
+            .with_pages(start_page_no, pages_amount);
 
-        let mut found_pages = FoundPages::new(start_page_no, pages_amount);
+        self.start_read_ahead(start_page_no, pages_amount);
 
-        for page_no in start_page_no..start_page_no + pages_amount {
-            if let Some(page) = write_access.pages_to_write.get_page(page_no) {
-                found_pages.add(Some(page));
-            } else if let Some(page) = write_access.cached_pages.get(page_no) {
-                found_pages.add(page.get_payload().into());
-            } else {
-                found_pages.add(None);
-            }
-        }
+        let result = self
+            .read_pages(start_page_no, pages_amount, &mut event)
+            .await;
 
-        let payload: Vec<u8>;
-
-        if let Some(pages_to_upload) = found_pages.get_pages_to_upload() {
-            payload = self
-                .page_blob
-                .get_pages(pages_to_upload.from_page_no, pages_to_upload.amount)
-                .await?;
-            found_pages.upload_missing_pages(payload.as_slice());
-        };
+        self.write_telemetry(event, result.as_ref().err()).await;
 
-        Ok(found_pages.into_vec())
+        result
     }
 
     async fn save_pages<'s>(
@@ -91,35 +628,800 @@ impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static> My
     ) -> Result<(), AzureStorageError> {
         let payload: AsSliceOrVec<'s, u8> = payload.into();
         let payload = payload.into_vec();
-        let mut write_access = self.cache.lock().await;
 
-        write_access
-            .pages_to_write
-            .update_pages(start_page_no, payload.clone());
+        let event = PageBlobTelemetryEvent::new(PageBlobOperation::SavePages)
+            .with_pages(start_page_no, payload.len() / BLOB_PAGE_SIZE);
 
-        write_access
-            .cached_pages
-            .update_cache(start_page_no, payload.as_slice());
-
-        Ok(())
+        self.execute_with_event(event, self.write_pages(start_page_no, payload))
+            .await
     }
 
     async fn delete(&self) -> Result<(), AzureStorageError> {
-        self.page_blob.delete().await
+        let event = PageBlobTelemetryEvent::new(PageBlobOperation::Delete);
+        self.execute_with_event(event, self.delete_blob()).await
     }
+
     async fn download(&self) -> Result<Vec<u8>, AzureStorageError> {
-        self.page_blob.download().await
+        let event = PageBlobTelemetryEvent::new(PageBlobOperation::Download);
+        self.execute_with_event(event, self.download_blob()).await
     }
 
     async fn get_blob_properties(&self) -> Result<PageBlobProperties, AzureStorageError> {
-        let mut cache = self.cache.lock().await;
-        if let Some(blob_properties) = &cache.page_blob_properties {
-            return Ok(blob_properties.clone());
+        let mut event = PageBlobTelemetryEvent::new(PageBlobOperation::GetBlobProperties);
+
+        let result = self.read_blob_properties(&mut event).await;
+
+        self.write_telemetry(event, result.as_ref().err()).await;
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use my_azure_storage_sdk::page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage};
+
+    use super::MyAzurePageBlobWithCache;
+    use crate::{
+        BackgroundFlusherSettings, DirtyDataBackpressure, DownloadMode, FaultInjectingPageBlob,
+        InMemoryPageBlob, PageBlobOperation, PendingWritesOnShrink, ReadAheadSettings,
+    };
+
+    async fn create_page_blob(pages_amount: usize) -> InMemoryPageBlob {
+        let page_blob = InMemoryPageBlob::new("container", "blob");
+        page_blob
+            .create_if_not_exists(pages_amount, true)
+            .await
+            .unwrap();
+        page_blob
+    }
+
+    #[tokio::test]
+    async fn test_pending_writes_are_flushed() {
+        let inner = create_page_blob(4).await;
+        let storage = inner.get_storage();
+
+        let page_blob = MyAzurePageBlobWithCache::new(inner);
+
+        page_blob
+            .save_pages(1, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        let content = storage.get_blob_content("container", "blob").unwrap();
+        assert_eq!(
+            [0u8; BLOB_PAGE_SIZE].as_slice(),
+            &content[BLOB_PAGE_SIZE..BLOB_PAGE_SIZE * 2]
+        );
+
+        let flush_result = page_blob.flush().await;
+
+        assert!(flush_result.is_ok());
+        assert_eq!(1, flush_result.get_flushed_pages_amount());
+
+        let content = storage.get_blob_content("container", "blob").unwrap();
+        assert_eq!(
+            [1u8; BLOB_PAGE_SIZE].as_slice(),
+            &content[BLOB_PAGE_SIZE..BLOB_PAGE_SIZE * 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_every_operation_is_written_to_telemetry() {
+        let page_blob =
+            MyAzurePageBlobWithCache::new(InMemoryPageBlob::new("container", "telemetry_blob"));
+
+        page_blob.create_container_if_not_exists().await.unwrap();
+        page_blob.create_if_not_exists(2, true).await.unwrap();
+        page_blob.create(2).await.unwrap();
+        page_blob.resize(3).await.unwrap();
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+        page_blob.get_pages(0, 1).await.unwrap();
+        page_blob.get_blob_properties().await.unwrap();
+        page_blob.flush().await;
+        page_blob.download().await.unwrap();
+        page_blob.delete().await.unwrap();
+
+        assert_eq!(
+            vec![
+                "create_container_if_not_exists",
+                "create_if_not_exists",
+                "create",
+                "resize",
+                "save_pages",
+                "get_pages",
+                "get_blob_properties",
+                "flush",
+                "download",
+                "delete",
+            ],
+            crate::get_written_operations("container", "telemetry_blob")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_intervals_are_kept() {
+        let inner = create_page_blob(2).await;
+
+        let page_blob = MyAzurePageBlobWithCache::new(inner);
+
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        // Page 5 is out of the blob, so Azure rejects it
+        page_blob
+            .save_pages(5, vec![5u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        let flush_result = page_blob.flush().await;
+
+        assert_eq!(1, flush_result.flushed.len());
+        assert_eq!(1, flush_result.failed.len());
+        assert_eq!(5, flush_result.failed[0].from_page_no);
+
+        let flush_result = page_blob.flush().await;
+
+        assert_eq!(0, flush_result.flushed.len());
+        assert_eq!(1, flush_result.failed.len());
+    }
+
+    #[tokio::test]
+    async fn test_flush_does_not_block_reads_and_writes() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(2).await, 0)
+            .with_latency(Duration::from_millis(300));
+        let storage = inner.get_inner().get_storage();
+
+        let page_blob = Arc::new(MyAzurePageBlobWithCache::new(inner));
+
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        let flush = {
+            let page_blob = page_blob.clone();
+            tokio::spawn(async move { page_blob.flush().await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        tokio::time::timeout(
+            Duration::from_millis(100),
+            page_blob.save_pages(0, vec![2u8; BLOB_PAGE_SIZE]),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let content = tokio::time::timeout(Duration::from_millis(100), page_blob.get_pages(0, 1))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(vec![2u8; BLOB_PAGE_SIZE], content);
+
+        assert!(flush.await.unwrap().is_ok());
+
+        // The write which came during the flush is still pending
+        assert_eq!(
+            BLOB_PAGE_SIZE,
+            page_blob.get_cache_stats().await.dirty_bytes
+        );
+
+        let content = storage.get_blob_content("container", "blob").unwrap();
+        assert_eq!([1u8; BLOB_PAGE_SIZE].as_slice(), &content[..BLOB_PAGE_SIZE]);
+
+        assert!(page_blob.flush().await.is_ok());
+
+        let content = storage.get_blob_content("container", "blob").unwrap();
+        assert_eq!([2u8; BLOB_PAGE_SIZE].as_slice(), &content[..BLOB_PAGE_SIZE]);
+        assert_eq!(0, page_blob.get_cache_stats().await.dirty_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_read_cache_serves_repeated_reads() {
+        let inner = create_page_blob(4).await;
+        inner
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE * 4])
+            .await
+            .unwrap();
+
+        let page_blob = MyAzurePageBlobWithCache::builder(inner)
+            .cache_everything_else(10)
+            .build()
+            .unwrap();
+
+        let first_read = page_blob.get_pages(0, 2).await.unwrap();
+
+        // Changing remote content directly, so only the cached reads see the old one
+        page_blob
+            .page_blob
+            .save_pages(0, vec![2u8; BLOB_PAGE_SIZE * 4])
+            .await
+            .unwrap();
+
+        let second_read = page_blob.get_pages(0, 2).await.unwrap();
+        assert_eq!(first_read, second_read);
+
+        let not_cached_read = page_blob.get_pages(2, 1).await.unwrap();
+        assert_eq!(vec![2u8; BLOB_PAGE_SIZE], not_cached_read);
+    }
+
+    #[tokio::test]
+    async fn test_flush_survives_injected_failure() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(2).await, 0)
+            .fail_nth_call(1)
+            .only_for(vec![PageBlobOperation::SavePages]);
+
+        let page_blob = MyAzurePageBlobWithCache::new(inner);
+
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE * 2])
+            .await
+            .unwrap();
+
+        assert!(!page_blob.flush().await.is_ok());
+        assert!(page_blob.flush().await.is_ok());
+
+        assert_eq!(
+            vec![1u8; BLOB_PAGE_SIZE * 2],
+            page_blob.page_blob.get_inner().download().await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_and_create_drop_cached_data() {
+        let inner = create_page_blob(2).await;
+
+        let page_blob = MyAzurePageBlobWithCache::builder(inner)
+            .cache_everything_else(10)
+            .build()
+            .unwrap();
+
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        page_blob.delete().await.unwrap();
+        page_blob.create(2).await.unwrap();
+
+        assert!(page_blob.flush().await.flushed.is_empty());
+        assert_eq!(
+            vec![0u8; BLOB_PAGE_SIZE * 2],
+            page_blob.get_pages(0, 2).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shrink_discards_pending_writes_beyond_the_end() {
+        let page_blob = MyAzurePageBlobWithCache::new(create_page_blob(4).await);
+
+        page_blob
+            .save_pages(2, vec![1u8; BLOB_PAGE_SIZE * 2])
+            .await
+            .unwrap();
+
+        page_blob.resize(3).await.unwrap();
+
+        let flush_result = page_blob.flush().await;
+        assert!(flush_result.is_ok());
+        assert_eq!(1, flush_result.get_flushed_pages_amount());
+
+        let properties = page_blob.get_blob_properties().await.unwrap();
+        assert_eq!(BLOB_PAGE_SIZE * 3, properties.blob_properties.blob_size);
+    }
+
+    #[tokio::test]
+    async fn test_shrink_is_rejected_with_pending_writes_beyond_the_end() {
+        let page_blob = MyAzurePageBlobWithCache::new(create_page_blob(4).await)
+            .with_pending_writes_on_shrink(PendingWritesOnShrink::Reject);
+
+        page_blob
+            .save_pages(3, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        assert!(page_blob.resize(3).await.is_err());
+        assert!(page_blob.resize(4).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_download_overlays_pending_writes() {
+        let inner = create_page_blob(3).await;
+        let storage = inner.get_storage();
+
+        let page_blob = MyAzurePageBlobWithCache::new(inner);
+
+        page_blob
+            .save_pages(1, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        let mut expected = vec![0u8; BLOB_PAGE_SIZE];
+        expected.extend_from_slice([1u8; BLOB_PAGE_SIZE].as_slice());
+        expected.extend_from_slice([0u8; BLOB_PAGE_SIZE].as_slice());
+
+        assert_eq!(expected, page_blob.download().await.unwrap());
+
+        // Write is still pending
+        let remote_content = storage.get_blob_content("container", "blob").unwrap();
+        assert_eq!(vec![0u8; BLOB_PAGE_SIZE * 3], remote_content);
+    }
+
+    #[tokio::test]
+    async fn test_download_does_not_hold_the_lock() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(2).await, 0)
+            .with_latency(Duration::from_millis(300))
+            .only_for(vec![PageBlobOperation::Download]);
+
+        let page_blob = Arc::new(
+            MyAzurePageBlobWithCache::builder(inner)
+                .cache_everything_else(10)
+                .build()
+                .unwrap(),
+        );
+
+        page_blob.get_pages(0, 1).await.unwrap();
+
+        let download = {
+            let page_blob = page_blob.clone();
+            tokio::spawn(async move { page_blob.download().await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        tokio::time::timeout(
+            Duration::from_millis(100),
+            page_blob.save_pages(1, vec![1u8; BLOB_PAGE_SIZE]),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        tokio::time::timeout(Duration::from_millis(100), page_blob.get_pages(0, 1))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut expected = vec![0u8; BLOB_PAGE_SIZE];
+        expected.extend_from_slice([1u8; BLOB_PAGE_SIZE].as_slice());
+
+        assert_eq!(expected, download.await.unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_flush_during_download_does_not_lose_pending_writes() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(2).await, 0)
+            .with_latency(Duration::from_millis(300))
+            .only_for(vec![PageBlobOperation::Download]);
+
+        let page_blob = Arc::new(MyAzurePageBlobWithCache::new(inner));
+
+        page_blob
+            .save_pages(1, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        let download = {
+            let page_blob = page_blob.clone();
+            tokio::spawn(async move { page_blob.download().await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(page_blob.flush().await.is_ok());
+
+        let mut expected = vec![0u8; BLOB_PAGE_SIZE];
+        expected.extend_from_slice([1u8; BLOB_PAGE_SIZE].as_slice());
+
+        assert_eq!(expected, download.await.unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_create_waits_for_flush_in_progress() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(2).await, 0)
+            .with_latency(Duration::from_millis(300))
+            .only_for(vec![PageBlobOperation::SavePages]);
+        let storage = inner.get_inner().get_storage();
+
+        let page_blob = Arc::new(MyAzurePageBlobWithCache::new(inner));
+
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        let flush = {
+            let page_blob = page_blob.clone();
+            tokio::spawn(async move { page_blob.flush().await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        page_blob.create(2).await.unwrap();
+
+        assert!(flush.await.unwrap().is_ok());
+
+        // Upload of the old content is done before the blob is replaced
+        assert_eq!(
+            vec![0u8; BLOB_PAGE_SIZE * 2],
+            storage.get_blob_content("container", "blob").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_flushes_first() {
+        let inner = create_page_blob(2).await;
+        let storage = inner.get_storage();
+
+        let page_blob = MyAzurePageBlobWithCache::new(inner)
+            .with_download_mode(DownloadMode::FlushFirst)
+            .with_cache_warming_on_download(true);
+
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        let mut expected = vec![1u8; BLOB_PAGE_SIZE];
+        expected.extend_from_slice([0u8; BLOB_PAGE_SIZE].as_slice());
+
+        assert_eq!(expected, page_blob.download().await.unwrap());
+        assert_eq!(
+            expected,
+            storage.get_blob_content("container", "blob").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_download_does_not_block_cached_reads_and_writes() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(3).await, 0)
+            .with_latency(Duration::from_millis(500));
+
+        let page_blob = Arc::new(MyAzurePageBlobWithCache::new(inner));
+
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        let slow_read = {
+            let page_blob = page_blob.clone();
+            tokio::spawn(async move { page_blob.get_pages(1, 1).await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let cached = tokio::time::timeout(Duration::from_millis(200), page_blob.get_pages(0, 1))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(vec![1u8; BLOB_PAGE_SIZE], cached);
+
+        tokio::time::timeout(
+            Duration::from_millis(200),
+            page_blob.save_pages(2, vec![2u8; BLOB_PAGE_SIZE]),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(vec![0u8; BLOB_PAGE_SIZE], slow_read.await.unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_write_during_download_is_not_overridden() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(1).await, 0)
+            .with_latency(Duration::from_millis(200));
+
+        let page_blob = Arc::new(MyAzurePageBlobWithCache::new(inner));
+
+        let slow_read = {
+            let page_blob = page_blob.clone();
+            tokio::spawn(async move { page_blob.get_pages(0, 1).await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        page_blob
+            .save_pages(0, vec![5u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        assert_eq!(vec![5u8; BLOB_PAGE_SIZE], slow_read.await.unwrap().unwrap());
+
+        assert!(page_blob.flush().await.is_ok());
+
+        // Stale downloaded page must not get into the read cache
+        assert_eq!(
+            vec![5u8; BLOB_PAGE_SIZE],
+            page_blob.get_pages(0, 1).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_share_one_download() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(4).await, 0)
+            .with_latency(Duration::from_millis(100));
+
+        let page_blob = Arc::new(MyAzurePageBlobWithCache::new(inner));
+
+        let mut tasks = Vec::new();
+
+        for _ in 0..4 {
+            let page_blob = page_blob.clone();
+            tasks.push(tokio::spawn(async move { page_blob.get_pages(0, 4).await }));
         }
-        let page_blob_properties = self.page_blob.get_blob_properties().await?;
 
-        cache.update_blob_properties(page_blob_properties.clone());
+        for task in tasks {
+            assert_eq!(vec![0u8; BLOB_PAGE_SIZE * 4], task.await.unwrap().unwrap());
+        }
 
-        Ok(page_blob_properties)
+        assert_eq!(1, page_blob.page_blob.get_calls_amount());
+    }
+
+    #[tokio::test]
+    async fn test_sequential_reads_are_prefetched() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(8).await, 0);
+
+        let page_blob = MyAzurePageBlobWithCache::builder(inner)
+            .cache_everything_else(100)
+            .with_read_ahead(ReadAheadSettings::new(4))
+            .build()
+            .unwrap();
+
+        page_blob.get_pages(0, 1).await.unwrap();
+        page_blob.get_pages(1, 1).await.unwrap();
+
+        page_blob.wait_for_prefetch().await;
+
+        let calls_amount = page_blob.page_blob.get_calls_amount();
+
+        for page_no in 2..4 {
+            page_blob.get_pages(page_no, 1).await.unwrap();
+        }
+
+        assert_eq!(calls_amount, page_blob.page_blob.get_calls_amount());
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_window_is_clamped_to_the_blob_size() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(8).await, 0);
+
+        let page_blob = MyAzurePageBlobWithCache::builder(inner)
+            .cache_everything_else(100)
+            .with_read_ahead(ReadAheadSettings::new(100))
+            .build()
+            .unwrap();
+
+        page_blob.get_pages(0, 1).await.unwrap();
+        page_blob.get_pages(1, 1).await.unwrap();
+
+        page_blob.wait_for_prefetch().await;
+
+        let calls_amount = page_blob.page_blob.get_calls_amount();
+
+        page_blob.get_pages(2, 6).await.unwrap();
+
+        assert_eq!(calls_amount, page_blob.page_blob.get_calls_amount());
+    }
+
+    #[tokio::test]
+    async fn test_nothing_is_prefetched_without_read_cache() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(8).await, 0);
+
+        let page_blob =
+            MyAzurePageBlobWithCache::new(inner).with_read_ahead(ReadAheadSettings::new(4));
+
+        page_blob.get_pages(0, 1).await.unwrap();
+        page_blob.get_pages(1, 1).await.unwrap();
+
+        page_blob.wait_for_prefetch().await;
+
+        assert_eq!(2, page_blob.page_blob.get_calls_amount());
+    }
+
+    #[tokio::test]
+    async fn test_random_reads_are_not_prefetched() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(8).await, 0);
+
+        let page_blob = MyAzurePageBlobWithCache::builder(inner)
+            .cache_everything_else(100)
+            .with_read_ahead(ReadAheadSettings::new(4))
+            .build()
+            .unwrap();
+
+        page_blob.get_pages(0, 1).await.unwrap();
+        page_blob.get_pages(5, 1).await.unwrap();
+        page_blob.get_pages(2, 1).await.unwrap();
+
+        page_blob.wait_for_prefetch().await;
+
+        assert_eq!(3, page_blob.page_blob.get_calls_amount());
+    }
+
+    #[tokio::test]
+    async fn test_pending_writes_over_budget_are_flushed_inline() {
+        let inner = create_page_blob(4).await;
+        let storage = inner.get_storage();
+
+        let page_blob = MyAzurePageBlobWithCache::builder(inner)
+            .with_max_cache_size(BLOB_PAGE_SIZE * 2)
+            .build()
+            .unwrap();
+
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE * 2])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            vec![0u8; BLOB_PAGE_SIZE * 4],
+            storage.get_blob_content("container", "blob").unwrap()
+        );
+
+        page_blob
+            .save_pages(2, vec![2u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        let mut expected = vec![1u8; BLOB_PAGE_SIZE * 2];
+        expected.extend_from_slice([2u8; BLOB_PAGE_SIZE].as_slice());
+        expected.extend_from_slice([0u8; BLOB_PAGE_SIZE].as_slice());
+
+        assert_eq!(
+            expected,
+            storage.get_blob_content("container", "blob").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_writes_over_budget_wait_for_flusher() {
+        let inner = create_page_blob(4).await;
+        let storage = inner.get_storage();
+
+        let page_blob = Arc::new(
+            MyAzurePageBlobWithCache::builder(inner)
+                .with_max_cache_size(BLOB_PAGE_SIZE)
+                .with_dirty_data_backpressure(DirtyDataBackpressure::WaitForFlusher)
+                .build()
+                .unwrap(),
+        );
+
+        let flusher = page_blob
+            .start_background_flusher(BackgroundFlusherSettings::new(Duration::from_secs(60)));
+
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        // Second write does not fit the budget, so the first one is flushed before it is accepted
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            page_blob.save_pages(1, vec![2u8; BLOB_PAGE_SIZE]),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let content = storage.get_blob_content("container", "blob").unwrap();
+        assert_eq!([1u8; BLOB_PAGE_SIZE].as_slice(), &content[..BLOB_PAGE_SIZE]);
+
+        assert!(flusher.stop().await.unwrap().is_ok());
+
+        let content = storage.get_blob_content("container", "blob").unwrap();
+        assert_eq!(
+            [2u8; BLOB_PAGE_SIZE].as_slice(),
+            &content[BLOB_PAGE_SIZE..BLOB_PAGE_SIZE * 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_writes_over_budget_are_flushed_inline_without_flusher() {
+        let inner = create_page_blob(4).await;
+        let storage = inner.get_storage();
+
+        let page_blob = Arc::new(
+            MyAzurePageBlobWithCache::builder(inner)
+                .with_max_cache_size(BLOB_PAGE_SIZE)
+                .with_dirty_data_backpressure(DirtyDataBackpressure::WaitForFlusher)
+                .build()
+                .unwrap(),
+        );
+
+        for page_no in 0..2 {
+            tokio::time::timeout(
+                Duration::from_secs(5),
+                page_blob.save_pages(page_no, vec![1u8; BLOB_PAGE_SIZE * 2]),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        }
+
+        let flusher = page_blob
+            .start_background_flusher(BackgroundFlusherSettings::new(Duration::from_secs(60)));
+        assert!(flusher.stop().await.unwrap().is_ok());
+
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            page_blob.save_pages(2, vec![2u8; BLOB_PAGE_SIZE * 2]),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let mut expected = vec![1u8; BLOB_PAGE_SIZE * 2];
+        expected.extend_from_slice([2u8; BLOB_PAGE_SIZE * 2].as_slice());
+
+        assert_eq!(
+            expected,
+            storage.get_blob_content("container", "blob").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_inline_flush_keeps_the_write() {
+        let inner = FaultInjectingPageBlob::new(create_page_blob(4).await, 0)
+            .fail_percentage(100.0)
+            .only_for(vec![PageBlobOperation::SavePages]);
+
+        let page_blob = MyAzurePageBlobWithCache::builder(inner)
+            .with_max_cache_size(BLOB_PAGE_SIZE)
+            .build()
+            .unwrap();
+
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE * 2])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            vec![1u8; BLOB_PAGE_SIZE * 2],
+            page_blob.get_pages(0, 2).await.unwrap()
+        );
+
+        assert!(!page_blob.flush().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cache_stats() {
+        let inner = create_page_blob(4).await;
+
+        let page_blob = MyAzurePageBlobWithCache::builder(inner)
+            .cache_everything_else(100)
+            .build()
+            .unwrap();
+
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE])
+            .await
+            .unwrap();
+
+        page_blob.get_pages(0, 2).await.unwrap();
+        page_blob.get_pages(0, 2).await.unwrap();
+
+        let stats = page_blob.get_and_reset_cache_stats().await;
+
+        assert_eq!(2, stats.reads);
+        assert_eq!(1, stats.hits);
+        assert_eq!(1, stats.remote_fetches);
+        assert_eq!(BLOB_PAGE_SIZE as u64, stats.bytes_from_remote);
+        assert_eq!(BLOB_PAGE_SIZE as u64 * 2, stats.bytes_from_pending_writes);
+        assert_eq!(BLOB_PAGE_SIZE as u64, stats.bytes_from_cached_pages);
+        assert_eq!(BLOB_PAGE_SIZE, stats.dirty_bytes);
+
+        let stats = page_blob.get_cache_stats().await;
+        assert_eq!(0, stats.reads);
+        assert_eq!(BLOB_PAGE_SIZE, stats.dirty_bytes);
     }
 }
@@ -0,0 +1,318 @@
+use std::{collections::HashMap, sync::Arc};
+
+use my_azure_storage_sdk::page_blob::MyAzurePageBlobStorage;
+use my_telemetry::MyTelemetryContext;
+
+use crate::{
+    pages_cache_list::{CachedPagesList, EvictionPolicy},
+    DirtyDataBackpressure, DownloadMode, MyAzurePageBlobWithCache, PageBlobCachedData,
+    PendingWritesOnShrink, ReadAheadSettings,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct CachedPagesInterval {
+    pub from_page_id: usize,
+    /// Inclusive
+    pub to_page_id: usize,
+    pub max_pages_amount: usize,
+}
+
+#[derive(Debug)]
+pub enum CacheConfigurationError {
+    InvalidInterval(CachedPagesInterval),
+    ZeroPagesBudget(CachedPagesInterval),
+    OverlappingIntervals(CachedPagesInterval, CachedPagesInterval),
+}
+
+pub struct MyAzurePageBlobWithCacheBuilder<
+    TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static,
+> {
+    page_blob: TMyAzurePageBlobStorage,
+    intervals: Vec<CachedPagesInterval>,
+    default_interval_max_pages_amount: Option<usize>,
+    eviction_policies: HashMap<usize, Arc<dyn EvictionPolicy>>,
+    default_eviction_policy: Option<Arc<dyn EvictionPolicy>>,
+    flush_parallelism: usize,
+    telemetry: Option<MyTelemetryContext>,
+    pending_writes_on_shrink: PendingWritesOnShrink,
+    download_mode: DownloadMode,
+    warm_cache_on_download: bool,
+    read_ahead: Option<ReadAheadSettings>,
+    max_cache_size: Option<usize>,
+    dirty_data_backpressure: DirtyDataBackpressure,
+}
+
+impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static>
+    MyAzurePageBlobWithCacheBuilder<TMyAzurePageBlobStorage>
+{
+    pub fn new(page_blob: TMyAzurePageBlobStorage) -> Self {
+        Self {
+            page_blob,
+            intervals: Vec::new(),
+            default_interval_max_pages_amount: None,
+            eviction_policies: HashMap::new(),
+            default_eviction_policy: None,
+            flush_parallelism: 1,
+            telemetry: None,
+            pending_writes_on_shrink: PendingWritesOnShrink::default(),
+            download_mode: DownloadMode::default(),
+            warm_cache_on_download: false,
+            read_ahead: None,
+            max_cache_size: None,
+            dirty_data_backpressure: DirtyDataBackpressure::default(),
+        }
+    }
+
+    /// Declares hot range of pages [from_page_id..=to_page_id] which keeps up to max_pages_amount pages in cache
+    pub fn add_cached_interval(
+        mut self,
+        from_page_id: usize,
+        to_page_id: usize,
+        max_pages_amount: usize,
+    ) -> Self {
+        self.intervals.push(CachedPagesInterval {
+            from_page_id,
+            to_page_id,
+            max_pages_amount,
+        });
+        self
+    }
+
+    /// Same as add_cached_interval, but pages of the interval are evicted by the given policy
+    pub fn add_cached_interval_with_eviction_policy(
+        mut self,
+        from_page_id: usize,
+        to_page_id: usize,
+        max_pages_amount: usize,
+        eviction_policy: impl EvictionPolicy + 'static,
+    ) -> Self {
+        // Intervals which start from the same page overlap, so the build fails for them anyway
+        self.eviction_policies
+            .insert(from_page_id, Arc::new(eviction_policy));
+        self.add_cached_interval(from_page_id, to_page_id, max_pages_amount)
+    }
+
+    /// Sets the eviction policy for intervals which are declared without one. FIFO is used by default
+    pub fn with_eviction_policy(mut self, eviction_policy: impl EvictionPolicy + 'static) -> Self {
+        self.default_eviction_policy = Some(Arc::new(eviction_policy));
+        self
+    }
+
+    /// Caches up to max_pages_amount pages which do not belong to any of declared intervals
+    pub fn cache_everything_else(mut self, max_pages_amount: usize) -> Self {
+        self.default_interval_max_pages_amount = Some(max_pages_amount);
+        self
+    }
+
+    pub fn with_flush_parallelism(mut self, flush_parallelism: usize) -> Self {
+        self.flush_parallelism = flush_parallelism;
+        self
+    }
+
+    pub fn with_telemetry(mut self, telemetry: MyTelemetryContext) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    pub fn with_pending_writes_on_shrink(
+        mut self,
+        pending_writes_on_shrink: PendingWritesOnShrink,
+    ) -> Self {
+        self.pending_writes_on_shrink = pending_writes_on_shrink;
+        self
+    }
+
+    pub fn with_download_mode(mut self, download_mode: DownloadMode) -> Self {
+        self.download_mode = download_mode;
+        self
+    }
+
+    pub fn with_cache_warming_on_download(mut self, warm_cache_on_download: bool) -> Self {
+        self.warm_cache_on_download = warm_cache_on_download;
+        self
+    }
+
+    pub fn with_read_ahead(mut self, settings: ReadAheadSettings) -> Self {
+        self.read_ahead = Some(settings);
+        self
+    }
+
+    /// Limits memory in bytes taken by cached pages and pending writes of all intervals together
+    pub fn with_max_cache_size(mut self, max_cache_size: usize) -> Self {
+        self.max_cache_size = Some(max_cache_size);
+        self
+    }
+
+    pub fn with_dirty_data_backpressure(
+        mut self,
+        dirty_data_backpressure: DirtyDataBackpressure,
+    ) -> Self {
+        self.dirty_data_backpressure = dirty_data_backpressure;
+        self
+    }
+
+    fn create_cached_pages_list(
+        &self,
+        max_pages_amount: usize,
+        from_page_id: usize,
+        to_page_id: usize,
+        eviction_policy: Option<&Arc<dyn EvictionPolicy>>,
+    ) -> CachedPagesList {
+        let result = CachedPagesList::new(max_pages_amount, from_page_id, to_page_id);
+
+        let eviction_policy = eviction_policy.or(self.default_eviction_policy.as_ref());
+
+        match eviction_policy {
+            Some(eviction_policy) => result.with_eviction_policy(eviction_policy.clone()),
+            None => result,
+        }
+    }
+
+    pub fn build(
+        mut self,
+    ) -> Result<MyAzurePageBlobWithCache<TMyAzurePageBlobStorage>, CacheConfigurationError> {
+        validate_intervals(&mut self.intervals)?;
+
+        let mut cached_data = PageBlobCachedData::new();
+        cached_data.max_size = self.max_cache_size;
+
+        for interval in &self.intervals {
+            let cached_pages_list = self.create_cached_pages_list(
+                interval.max_pages_amount,
+                interval.from_page_id,
+                interval.to_page_id,
+                self.eviction_policies.get(&interval.from_page_id),
+            );
+
+            cached_data
+                .cached_pages
+                .add_cached_pages_list(cached_pages_list);
+        }
+
+        if let Some(max_pages_amount) = self.default_interval_max_pages_amount {
+            if max_pages_amount == 0 {
+                return Err(CacheConfigurationError::ZeroPagesBudget(
+                    CachedPagesInterval {
+                        from_page_id: 0,
+                        to_page_id: usize::MAX,
+                        max_pages_amount,
+                    },
+                ));
+            }
+
+            let cached_pages_list =
+                self.create_cached_pages_list(max_pages_amount, 0, usize::MAX, None);
+
+            cached_data
+                .cached_pages
+                .set_default_cached_pages_list(cached_pages_list);
+        }
+
+        let mut result = MyAzurePageBlobWithCache::from_cached_data(self.page_blob, cached_data)
+            .with_flush_parallelism(self.flush_parallelism)
+            .with_pending_writes_on_shrink(self.pending_writes_on_shrink)
+            .with_download_mode(self.download_mode)
+            .with_cache_warming_on_download(self.warm_cache_on_download)
+            .with_dirty_data_backpressure(self.dirty_data_backpressure);
+
+        if let Some(telemetry) = self.telemetry {
+            result = result.with_telemetry(telemetry);
+        }
+
+        if let Some(read_ahead) = self.read_ahead {
+            result = result.with_read_ahead(read_ahead);
+        }
+
+        Ok(result)
+    }
+}
+
+fn validate_intervals(
+    intervals: &mut Vec<CachedPagesInterval>,
+) -> Result<(), CacheConfigurationError> {
+    for interval in intervals.iter() {
+        if interval.from_page_id > interval.to_page_id {
+            return Err(CacheConfigurationError::InvalidInterval(*interval));
+        }
+
+        if interval.max_pages_amount == 0 {
+            return Err(CacheConfigurationError::ZeroPagesBudget(*interval));
+        }
+    }
+
+    intervals.sort_by_key(|interval| interval.from_page_id);
+
+    for window in intervals.windows(2) {
+        if window[0].to_page_id >= window[1].from_page_id {
+            return Err(CacheConfigurationError::OverlappingIntervals(
+                window[0], window[1],
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(from_page_id: usize, to_page_id: usize) -> CachedPagesInterval {
+        CachedPagesInterval {
+            from_page_id,
+            to_page_id,
+            max_pages_amount: 10,
+        }
+    }
+
+    #[test]
+    fn test_valid_intervals_are_sorted() {
+        let mut intervals = vec![interval(10, 20), interval(0, 5), interval(6, 9)];
+
+        validate_intervals(&mut intervals).unwrap();
+
+        assert_eq!(0, intervals[0].from_page_id);
+        assert_eq!(6, intervals[1].from_page_id);
+        assert_eq!(10, intervals[2].from_page_id);
+    }
+
+    #[test]
+    fn test_overlapping_intervals_are_rejected() {
+        let mut intervals = vec![interval(0, 10), interval(10, 20)];
+
+        let result = validate_intervals(&mut intervals);
+
+        assert!(matches!(
+            result,
+            Err(CacheConfigurationError::OverlappingIntervals(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_reversed_interval_is_rejected() {
+        let mut intervals = vec![interval(10, 0)];
+
+        let result = validate_intervals(&mut intervals);
+
+        assert!(matches!(
+            result,
+            Err(CacheConfigurationError::InvalidInterval(_))
+        ));
+    }
+
+    #[test]
+    fn test_zero_pages_budget_is_rejected() {
+        let mut intervals = vec![CachedPagesInterval {
+            from_page_id: 0,
+            to_page_id: 10,
+            max_pages_amount: 0,
+        }];
+
+        let result = validate_intervals(&mut intervals);
+
+        assert!(matches!(
+            result,
+            Err(CacheConfigurationError::ZeroPagesBudget(_))
+        ));
+    }
+}
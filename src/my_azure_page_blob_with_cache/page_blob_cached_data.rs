@@ -3,12 +3,22 @@ use my_azure_storage_sdk::{
     page_blob::{consts::BLOB_PAGE_SIZE, PageBlobProperties},
 };
 
-use crate::{pages_cache_list::PagesCache, PagesCacheIntervals};
+use std::sync::Arc;
+
+use crate::{
+    pages_cache_list::PagesCache, CacheStatsCounters, FoundPages, MissingInterval,
+    PagesCacheIntervals, PagesRange,
+};
 
 pub struct PageBlobCachedData {
     pub page_blob_properties: Option<PageBlobProperties>,
     pub cached_pages: PagesCache,
     pub pages_to_write: PagesCacheIntervals,
+    /// Changes every time content of the blob is changed. Allows to detect writes which happened while the lock was released
+    pub write_generation: u64,
+    /// Budget in bytes for cached pages and pending writes together
+    pub max_size: Option<usize>,
+    pub stats: Arc<CacheStatsCounters>,
 }
 
 impl PageBlobCachedData {
@@ -17,6 +27,9 @@ impl PageBlobCachedData {
             page_blob_properties: None,
             cached_pages: PagesCache::new(),
             pages_to_write: PagesCacheIntervals::new(),
+            write_generation: 0,
+            max_size: None,
+            stats: Arc::new(CacheStatsCounters::new()),
         }
     }
     pub fn update_pages_amount(&mut self, pages_amount: usize) {
@@ -31,4 +44,486 @@ impl PageBlobCachedData {
     pub fn update_blob_properties(&mut self, blob_properties: PageBlobProperties) {
         self.page_blob_properties = Some(blob_properties);
     }
+
+    pub fn save_pages(&mut self, start_page_no: usize, payload: Vec<u8>) {
+        let evicted = self
+            .cached_pages
+            .update_cache(start_page_no, payload.as_slice());
+        self.stats.add_evicted_pages(evicted);
+
+        self.pages_to_write.update_pages(start_page_no, payload);
+        self.write_generation += 1;
+        self.gc();
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.cached_pages.get_size() + self.pages_to_write.get_dirty_size()
+    }
+
+    /// Pending writes can not be evicted, so cached pages get what is left of the budget
+    pub fn gc(&mut self) {
+        let Some(max_size) = self.max_size else {
+            return;
+        };
+
+        let dirty_size = self.pages_to_write.get_dirty_size();
+        let evicted = self
+            .cached_pages
+            .gc_to_size(max_size.saturating_sub(dirty_size));
+        self.stats.add_evicted_pages(evicted);
+    }
+
+    /// Puts the content of the blob starting from start_page_no to the read cache
+    pub fn warm_cache(&mut self, start_page_no: usize, payload: &[u8]) {
+        let evicted = self.cached_pages.update_cache(start_page_no, payload);
+        self.stats.add_evicted_pages(evicted);
+        self.gc();
+    }
+
+    /// Returns true if pending writes with extra_size bytes more do not fit the budget.
+    /// A write is never blocked if there are no pending writes, even if it is bigger than the budget
+    pub fn is_dirty_size_over_budget(&self, extra_size: usize) -> bool {
+        let Some(max_size) = self.max_size else {
+            return false;
+        };
+
+        if self.pages_to_write.is_empty() {
+            return false;
+        }
+
+        self.pages_to_write.get_dirty_size() + extra_size > max_size
+    }
+
+    /// Forgets everything about the blob. Pending writes are discarded
+    pub fn clear(&mut self) {
+        self.page_blob_properties = None;
+        self.cached_pages.clear();
+        self.pages_to_write.clear();
+        self.write_generation += 1;
+    }
+
+    /// Drops cached pages and pending writes which are beyond the new end of the blob
+    pub fn truncate(&mut self, pages_amount: usize) {
+        self.cached_pages.remove_pages_from(pages_amount);
+        self.pages_to_write.truncate(pages_amount);
+        self.update_pages_amount(pages_amount);
+        self.write_generation += 1;
+    }
+
+    /// Pending writes take precedence over the read cache
+    pub fn get_page(&self, page_no: usize) -> Option<&[u8]> {
+        if let Some(page) = self.pages_to_write.get_page(page_no) {
+            return Some(page);
+        }
+
+        let page = self.cached_pages.get(page_no)?;
+        Some(page.get_payload())
+    }
+
+    /// Same as find_pages, but found bytes are counted in the statistics and found pages are counted
+    /// for the eviction policy. Has to be called once per user read, so retried lookups do not count the hits again
+    pub fn lookup_pages(&self, start_page_no: usize, pages_amount: usize) -> FoundPages<'_> {
+        self.collect_pages(start_page_no, pages_amount, true)
+    }
+
+    pub fn find_pages(&self, start_page_no: usize, pages_amount: usize) -> FoundPages<'_> {
+        self.collect_pages(start_page_no, pages_amount, false)
+    }
+
+    /// Pending writes are taken range by range. Only gaps between them are looked up in the read cache
+    fn collect_pages(
+        &self,
+        start_page_no: usize,
+        pages_amount: usize,
+        is_user_read: bool,
+    ) -> FoundPages<'_> {
+        let mut found_pages = FoundPages::new(start_page_no, pages_amount);
+
+        for range in self.pages_to_write.get_range(start_page_no, pages_amount) {
+            match range {
+                PagesRange::Covered { content, .. } => {
+                    if is_user_read {
+                        self.stats.add_bytes_from_pending_writes(content.len());
+                    }
+
+                    for page in content.chunks_exact(BLOB_PAGE_SIZE) {
+                        found_pages.add(Some(page));
+                    }
+                }
+                PagesRange::Uncovered {
+                    from_page_no,
+                    amount,
+                } => {
+                    for page_no in from_page_no..from_page_no + amount {
+                        let page = if is_user_read {
+                            self.cached_pages.get(page_no)
+                        } else {
+                            self.cached_pages.peek(page_no)
+                        };
+
+                        let page = page.map(|page| page.get_payload());
+
+                        if is_user_read {
+                            if let Some(page) = page {
+                                self.stats.add_bytes_from_cached_pages(page.len());
+                            }
+                        }
+
+                        found_pages.add(page);
+                    }
+                }
+            }
+        }
+
+        found_pages
+    }
+
+    /// Assembles pages which were downloaded without holding the lock. Pages found in the cache win,
+    /// since they could be written while the download was in progress.
+    /// Payload has to cover the whole downloaded interval.
+    /// Returns the content and the intervals taken from the payload,
+    /// or None if some page is neither in the cache nor in the payload.
+    pub fn merge_downloaded_pages(
+        &self,
+        start_page_no: usize,
+        pages_amount: usize,
+        downloaded: &MissingInterval,
+        payload: &[u8],
+    ) -> Option<(Vec<u8>, Vec<MissingInterval>)> {
+        let mut found_pages = self.find_pages(start_page_no, pages_amount);
+
+        let downloaded_to_page_no = downloaded.from_page_no + downloaded.amount;
+
+        for missing_interval in &found_pages.missing_intervals {
+            if missing_interval.from_page_no < downloaded.from_page_no
+                || missing_interval.from_page_no + missing_interval.amount > downloaded_to_page_no
+            {
+                return None;
+            }
+        }
+
+        found_pages.upload_missing_pages_from(downloaded.from_page_no, payload);
+
+        let result = found_pages.into_vec();
+        Some((result, found_pages.missing_intervals))
+    }
+
+    /// Puts pending writes on top of the blob content. Pending writes beyond the content are ignored
+    pub fn overlay_pending_writes(&self, content: &mut [u8]) {
+        for item in self.pages_to_write.iter() {
+            let offset = item.page_id * BLOB_PAGE_SIZE;
+
+            if offset >= content.len() {
+                break;
+            }
+
+            let size = item.content.len().min(content.len() - offset);
+            content[offset..offset + size].copy_from_slice(&item.content[..size]);
+        }
+    }
+
+    /// Puts downloaded pages to the read cache. Only missing intervals are cached,
+    /// since the rest of the downloaded range can be overridden by pending writes.
+    pub fn cache_downloaded_pages(
+        &mut self,
+        download_from_page_no: usize,
+        missing_intervals: &[MissingInterval],
+        payload: &[u8],
+    ) {
+        for missing_interval in missing_intervals {
+            let offset = (missing_interval.from_page_no - download_from_page_no) * BLOB_PAGE_SIZE;
+            let size = missing_interval.amount * BLOB_PAGE_SIZE;
+
+            if offset + size > payload.len() {
+                break;
+            }
+
+            let evicted = self.cached_pages.update_cache(
+                missing_interval.from_page_no,
+                &payload[offset..offset + size],
+            );
+            self.stats.add_evicted_pages(evicted);
+        }
+
+        self.gc();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use my_azure_storage_sdk::page_blob::consts::BLOB_PAGE_SIZE;
+
+    use super::PageBlobCachedData;
+    use crate::MissingInterval;
+
+    #[test]
+    fn test_cache_downloaded_pages_caches_only_missing_intervals() {
+        let mut cached_data = PageBlobCachedData::new();
+        cached_data.cached_pages.add_interval_to_cache(0, 100, 100);
+
+        // pages 10 and 12 were missing, page 11 was found in pending writes
+        let missing_intervals = vec![
+            MissingInterval {
+                from_page_no: 10,
+                amount: 1,
+            },
+            MissingInterval {
+                from_page_no: 12,
+                amount: 1,
+            },
+        ];
+
+        let mut payload = vec![10u8; BLOB_PAGE_SIZE];
+        payload.extend_from_slice([11u8; BLOB_PAGE_SIZE].as_slice());
+        payload.extend_from_slice([12u8; BLOB_PAGE_SIZE].as_slice());
+
+        cached_data.cache_downloaded_pages(10, missing_intervals.as_slice(), payload.as_slice());
+
+        assert_eq!(
+            cached_data.cached_pages.get(10).unwrap().get_payload(),
+            [10u8; BLOB_PAGE_SIZE].as_slice()
+        );
+        assert!(cached_data.cached_pages.get(11).is_none());
+        assert_eq!(
+            cached_data.cached_pages.get(12).unwrap().get_payload(),
+            [12u8; BLOB_PAGE_SIZE].as_slice()
+        );
+    }
+
+    #[test]
+    fn test_cache_downloaded_pages_respects_max_pages_amount() {
+        let mut cached_data = PageBlobCachedData::new();
+        cached_data.cached_pages.add_interval_to_cache(0, 100, 2);
+
+        let missing_intervals = vec![MissingInterval {
+            from_page_no: 0,
+            amount: 4,
+        }];
+
+        let payload = vec![1u8; BLOB_PAGE_SIZE * 4];
+
+        cached_data.cache_downloaded_pages(0, missing_intervals.as_slice(), payload.as_slice());
+
+        let cached_amount = (0..4)
+            .filter(|page_no| cached_data.cached_pages.get(*page_no).is_some())
+            .count();
+
+        assert!(cached_amount <= 2);
+    }
+
+    #[test]
+    fn test_truncate_drops_pages_beyond_the_end() {
+        let mut cached_data = PageBlobCachedData::new();
+        cached_data.cached_pages.add_interval_to_cache(0, 100, 100);
+
+        cached_data
+            .cached_pages
+            .update_cache(0, vec![1u8; BLOB_PAGE_SIZE * 4].as_slice());
+        cached_data
+            .pages_to_write
+            .update_pages(2, vec![2u8; BLOB_PAGE_SIZE * 2]);
+
+        cached_data.truncate(3);
+
+        assert!(cached_data.cached_pages.get(2).is_some());
+        assert!(cached_data.cached_pages.get(3).is_none());
+        assert!(cached_data.pages_to_write.get_page(2).is_some());
+        assert!(cached_data.pages_to_write.get_page(3).is_none());
+
+        let blob_size = cached_data
+            .page_blob_properties
+            .as_ref()
+            .unwrap()
+            .blob_properties
+            .blob_size;
+
+        assert_eq!(BLOB_PAGE_SIZE * 3, blob_size);
+    }
+
+    #[test]
+    fn test_overlay_pending_writes() {
+        let mut cached_data = PageBlobCachedData::new();
+
+        cached_data
+            .pages_to_write
+            .update_pages(1, vec![1u8; BLOB_PAGE_SIZE]);
+        cached_data
+            .pages_to_write
+            .update_pages(3, vec![3u8; BLOB_PAGE_SIZE * 2]);
+        cached_data
+            .pages_to_write
+            .update_pages(10, vec![10u8; BLOB_PAGE_SIZE]);
+
+        let mut content = vec![0u8; BLOB_PAGE_SIZE * 4];
+
+        cached_data.overlay_pending_writes(content.as_mut_slice());
+
+        let mut expected = vec![0u8; BLOB_PAGE_SIZE];
+        expected.extend_from_slice([1u8; BLOB_PAGE_SIZE].as_slice());
+        expected.extend_from_slice([0u8; BLOB_PAGE_SIZE].as_slice());
+        expected.extend_from_slice([3u8; BLOB_PAGE_SIZE].as_slice());
+
+        assert_eq!(expected, content);
+    }
+
+    #[test]
+    fn test_merge_downloaded_pages_prefers_pages_written_meanwhile() {
+        let mut cached_data = PageBlobCachedData::new();
+
+        let downloaded = MissingInterval {
+            from_page_no: 0,
+            amount: 3,
+        };
+        let payload = vec![0u8; BLOB_PAGE_SIZE * 3];
+
+        cached_data.save_pages(1, vec![1u8; BLOB_PAGE_SIZE]);
+
+        let (result, downloaded_intervals) = cached_data
+            .merge_downloaded_pages(0, 3, &downloaded, payload.as_slice())
+            .unwrap();
+
+        let mut expected = vec![0u8; BLOB_PAGE_SIZE];
+        expected.extend_from_slice([1u8; BLOB_PAGE_SIZE].as_slice());
+        expected.extend_from_slice([0u8; BLOB_PAGE_SIZE].as_slice());
+
+        assert_eq!(expected, result);
+
+        assert_eq!(2, downloaded_intervals.len());
+        assert_eq!(0, downloaded_intervals[0].from_page_no);
+        assert_eq!(2, downloaded_intervals[1].from_page_no);
+    }
+
+    #[test]
+    fn test_merge_downloaded_pages_detects_pages_which_are_not_downloaded() {
+        let mut cached_data = PageBlobCachedData::new();
+        cached_data.save_pages(0, vec![1u8; BLOB_PAGE_SIZE]);
+
+        let downloaded = MissingInterval {
+            from_page_no: 1,
+            amount: 1,
+        };
+        let payload = vec![0u8; BLOB_PAGE_SIZE];
+
+        assert!(cached_data
+            .merge_downloaded_pages(0, 2, &downloaded, payload.as_slice())
+            .is_some());
+
+        // Page 0 is gone while page 1 was being downloaded
+        cached_data.clear();
+
+        assert!(cached_data
+            .merge_downloaded_pages(0, 2, &downloaded, payload.as_slice())
+            .is_none());
+    }
+
+    #[test]
+    fn test_find_pages_takes_gaps_between_pending_writes_from_cache() {
+        let mut cached_data = PageBlobCachedData::new();
+        cached_data.cached_pages.add_interval_to_cache(0, 100, 100);
+
+        let missing_intervals = vec![MissingInterval {
+            from_page_no: 0,
+            amount: 4,
+        }];
+        let payload = vec![0u8; BLOB_PAGE_SIZE * 4];
+        cached_data.cache_downloaded_pages(0, missing_intervals.as_slice(), payload.as_slice());
+
+        cached_data
+            .pages_to_write
+            .update_pages(1, vec![1u8; BLOB_PAGE_SIZE]);
+        cached_data
+            .pages_to_write
+            .update_pages(3, vec![3u8; BLOB_PAGE_SIZE * 2]);
+
+        let mut found_pages = cached_data.find_pages(0, 6);
+        assert_eq!(1, found_pages.missing_intervals.len());
+        assert_eq!(5, found_pages.missing_intervals[0].from_page_no);
+
+        let downloaded = vec![5u8; BLOB_PAGE_SIZE];
+        found_pages.upload_missing_pages(downloaded.as_slice());
+
+        let mut expected = vec![0u8; BLOB_PAGE_SIZE];
+        expected.extend_from_slice([1u8; BLOB_PAGE_SIZE].as_slice());
+        expected.extend_from_slice([0u8; BLOB_PAGE_SIZE].as_slice());
+        expected.extend_from_slice([3u8; BLOB_PAGE_SIZE * 2].as_slice());
+        expected.extend_from_slice([5u8; BLOB_PAGE_SIZE].as_slice());
+
+        assert_eq!(expected, found_pages.into_vec());
+    }
+
+    #[test]
+    fn test_write_generation_changes_on_writes() {
+        let mut cached_data = PageBlobCachedData::new();
+
+        let generation = cached_data.write_generation;
+        cached_data.save_pages(0, vec![1u8; BLOB_PAGE_SIZE]);
+        assert_ne!(generation, cached_data.write_generation);
+
+        let generation = cached_data.write_generation;
+        cached_data.truncate(0);
+        assert_ne!(generation, cached_data.write_generation);
+    }
+
+    #[test]
+    fn test_cached_pages_get_what_is_left_from_pending_writes() {
+        let mut cached_data = PageBlobCachedData::new();
+        cached_data.cached_pages.add_interval_to_cache(0, 100, 100);
+        cached_data.max_size = Some(BLOB_PAGE_SIZE * 6);
+
+        let missing_intervals = vec![MissingInterval {
+            from_page_no: 10,
+            amount: 4,
+        }];
+        let payload = vec![1u8; BLOB_PAGE_SIZE * 4];
+
+        cached_data.cache_downloaded_pages(10, missing_intervals.as_slice(), payload.as_slice());
+        assert_eq!(BLOB_PAGE_SIZE * 4, cached_data.get_size());
+
+        // Pending write takes 2 pages and its copy in the read cache takes 2 more
+        cached_data.save_pages(0, vec![2u8; BLOB_PAGE_SIZE * 2]);
+
+        assert!(cached_data.get_size() <= BLOB_PAGE_SIZE * 6);
+        assert_eq!(
+            BLOB_PAGE_SIZE * 2,
+            cached_data.pages_to_write.get_dirty_size()
+        );
+    }
+
+    #[test]
+    fn test_dirty_size_over_budget() {
+        let mut cached_data = PageBlobCachedData::new();
+        cached_data.max_size = Some(BLOB_PAGE_SIZE * 2);
+
+        // Nothing is pending, so even a big write is accepted
+        assert!(!cached_data.is_dirty_size_over_budget(BLOB_PAGE_SIZE * 3));
+
+        cached_data.save_pages(0, vec![1u8; BLOB_PAGE_SIZE]);
+
+        assert!(!cached_data.is_dirty_size_over_budget(BLOB_PAGE_SIZE));
+        assert!(cached_data.is_dirty_size_over_budget(BLOB_PAGE_SIZE * 2));
+    }
+
+    #[test]
+    fn test_lookup_pages_counts_bytes_by_source() {
+        let mut cached_data = PageBlobCachedData::new();
+        cached_data.cached_pages.add_interval_to_cache(0, 100, 100);
+
+        let missing_intervals = vec![MissingInterval {
+            from_page_no: 1,
+            amount: 1,
+        }];
+        let payload = vec![1u8; BLOB_PAGE_SIZE];
+        cached_data.cache_downloaded_pages(1, missing_intervals.as_slice(), payload.as_slice());
+
+        cached_data
+            .pages_to_write
+            .update_pages(0, vec![0u8; BLOB_PAGE_SIZE]);
+
+        let found_pages = cached_data.lookup_pages(0, 3);
+        assert_eq!(1, found_pages.missing_intervals.len());
+
+        let stats = cached_data.stats.get_snapshot(0, false);
+        assert_eq!(BLOB_PAGE_SIZE as u64, stats.bytes_from_pending_writes);
+        assert_eq!(BLOB_PAGE_SIZE as u64, stats.bytes_from_cached_pages);
+    }
 }
@@ -0,0 +1,14 @@
+/// What to do with pending writes which are beyond the new end of the blob when it is shrunk by resize
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingWritesOnShrink {
+    /// Pending writes beyond the new end are dropped
+    Discard,
+    /// Resize fails with AzureStorageError::InvalidPageRange and the blob stays untouched
+    Reject,
+}
+
+impl Default for PendingWritesOnShrink {
+    fn default() -> Self {
+        Self::Discard
+    }
+}
@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use my_azure_storage_sdk::{
+    page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage},
+    AzureStorageError,
+};
+use tokio::{sync::RwLock, task::JoinHandle};
+
+use crate::{CacheStatsCounters, PageBlobCachedData, SingleFlightReads};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReadAheadSettings {
+    /// Amount of pages which are prefetched after the last read page
+    pub window_pages_amount: usize,
+    /// Amount of reads which go one after another before the prefetch is started
+    pub sequential_reads_to_start: usize,
+}
+
+impl ReadAheadSettings {
+    pub fn new(window_pages_amount: usize) -> Self {
+        Self {
+            window_pages_amount,
+            sequential_reads_to_start: 2,
+        }
+    }
+
+    pub fn with_sequential_reads_to_start(mut self, sequential_reads_to_start: usize) -> Self {
+        self.sequential_reads_to_start = sequential_reads_to_start.max(1);
+        self
+    }
+}
+
+pub struct PrefetchInterval {
+    pub from_page_no: usize,
+    pub pages_amount: usize,
+}
+
+/// Detects sequential reads and keeps track of the prefetch which is in progress
+pub struct ReadAheadState {
+    settings: ReadAheadSettings,
+    next_page_no: usize,
+    sequential_reads: usize,
+    prefetched_to_page_no: usize,
+    prefetch: Option<JoinHandle<()>>,
+}
+
+impl ReadAheadState {
+    pub fn new(settings: ReadAheadSettings) -> Self {
+        Self {
+            settings,
+            next_page_no: 0,
+            sequential_reads: 0,
+            prefetched_to_page_no: 0,
+            prefetch: None,
+        }
+    }
+
+    /// Registers the read. Returns the interval to prefetch if the read continues the sequence
+    /// and less than a half of the window is prefetched ahead of it.
+    pub fn register_read(
+        &mut self,
+        start_page_no: usize,
+        pages_amount: usize,
+    ) -> Option<PrefetchInterval> {
+        if self.sequential_reads > 0 && start_page_no == self.next_page_no {
+            self.sequential_reads += 1;
+        } else {
+            // Pattern is broken. Prefetched pages are not needed anymore
+            self.cancel();
+            self.sequential_reads = 1;
+            self.prefetched_to_page_no = 0;
+        }
+
+        self.next_page_no = start_page_no + pages_amount;
+
+        if self.sequential_reads < self.settings.sequential_reads_to_start {
+            return None;
+        }
+
+        let window_end = self.next_page_no + self.settings.window_pages_amount;
+
+        if self.prefetched_to_page_no >= self.next_page_no + self.settings.window_pages_amount / 2 {
+            return None;
+        }
+
+        if let Some(prefetch) = &self.prefetch {
+            if !prefetch.is_finished() {
+                return None;
+            }
+        }
+
+        let from_page_no = self.prefetched_to_page_no.max(self.next_page_no);
+
+        if from_page_no >= window_end {
+            return None;
+        }
+
+        self.prefetched_to_page_no = window_end;
+
+        Some(PrefetchInterval {
+            from_page_no,
+            pages_amount: window_end - from_page_no,
+        })
+    }
+
+    pub fn set_prefetch(&mut self, prefetch: JoinHandle<()>) {
+        self.prefetch = Some(prefetch);
+    }
+
+    pub fn take_prefetch(&mut self) -> Option<JoinHandle<()>> {
+        self.prefetch.take()
+    }
+
+    pub fn cancel(&mut self) {
+        if let Some(prefetch) = self.prefetch.take() {
+            prefetch.abort();
+        }
+    }
+}
+
+impl Drop for ReadAheadState {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Downloads pages which are neither pending nor cached and puts them to the read cache
+pub(crate) async fn prefetch<
+    TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static,
+>(
+    page_blob: Arc<TMyAzurePageBlobStorage>,
+    cache: Arc<RwLock<PageBlobCachedData>>,
+    single_flight_reads: Arc<SingleFlightReads>,
+    stats: Arc<CacheStatsCounters>,
+    interval: PrefetchInterval,
+) {
+    // Downloaded pages would be dropped right away
+    if !cache
+        .read()
+        .await
+        .cached_pages
+        .can_cache(interval.from_page_no, interval.pages_amount)
+    {
+        return;
+    }
+
+    let Some(blob_pages_amount) = get_blob_pages_amount(page_blob.as_ref(), cache.as_ref()).await
+    else {
+        return;
+    };
+
+    let (pages_to_upload, write_generation) = {
+        let read_access = cache.read().await;
+
+        // Pages beyond the end of the blob can not be read
+        let pages_amount = blob_pages_amount
+            .saturating_sub(interval.from_page_no)
+            .min(interval.pages_amount);
+
+        if pages_amount == 0 {
+            return;
+        }
+
+        let found_pages = read_access.find_pages(interval.from_page_no, pages_amount);
+
+        let Some(pages_to_upload) = found_pages.get_pages_to_upload() else {
+            return;
+        };
+
+        (pages_to_upload, read_access.write_generation)
+    };
+
+    let payload = single_flight_reads
+        .get_pages(
+            pages_to_upload.from_page_no,
+            pages_to_upload.amount,
+            write_generation,
+            |from_page_no, pages_amount| {
+                let page_blob = &page_blob;
+                let stats = &stats;
+                async move {
+                    let payload = page_blob.get_pages(from_page_no, pages_amount).await?;
+                    stats.add_remote_fetch(payload.len());
+                    Ok::<_, AzureStorageError>(payload)
+                }
+            },
+        )
+        .await;
+
+    // Prefetch is best effort. The read which needs these pages reports the error
+    let Ok(payload) = payload else {
+        return;
+    };
+
+    let mut write_access = cache.write().await;
+
+    if write_access.write_generation != write_generation {
+        return;
+    }
+
+    let missing_intervals = write_access
+        .find_pages(pages_to_upload.from_page_no, pages_to_upload.amount)
+        .missing_intervals;
+
+    write_access.cache_downloaded_pages(
+        pages_to_upload.from_page_no,
+        missing_intervals.as_slice(),
+        payload.as_slice(),
+    );
+}
+
+async fn get_blob_pages_amount<
+    TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static,
+>(
+    page_blob: &TMyAzurePageBlobStorage,
+    cache: &RwLock<PageBlobCachedData>,
+) -> Option<usize> {
+    let write_generation = {
+        let read_access = cache.read().await;
+
+        if let Some(page_blob_properties) = &read_access.page_blob_properties {
+            return Some(page_blob_properties.blob_properties.blob_size / BLOB_PAGE_SIZE);
+        }
+
+        read_access.write_generation
+    };
+
+    let page_blob_properties = page_blob.get_blob_properties().await.ok()?;
+
+    let mut write_access = cache.write().await;
+
+    // The blob could be resized meanwhile. Next reads start the prefetch again
+    if write_access.write_generation != write_generation {
+        return None;
+    }
+
+    let blob_pages_amount = page_blob_properties.blob_properties.blob_size / BLOB_PAGE_SIZE;
+    write_access.update_blob_properties(page_blob_properties);
+
+    Some(blob_pages_amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReadAheadSettings, ReadAheadState};
+
+    #[test]
+    fn test_prefetch_starts_after_sequential_reads() {
+        let mut state = ReadAheadState::new(ReadAheadSettings::new(8));
+
+        assert!(state.register_read(0, 2).is_none());
+
+        let interval = state.register_read(2, 2).unwrap();
+        assert_eq!(4, interval.from_page_no);
+        assert_eq!(8, interval.pages_amount);
+
+        // Enough pages are prefetched ahead
+        assert!(state.register_read(4, 2).is_none());
+        assert!(state.register_read(6, 2).is_none());
+
+        // Less than a half of the window is left, so the window is moved
+        let interval = state.register_read(8, 2).unwrap();
+        assert_eq!(12, interval.from_page_no);
+        assert_eq!(6, interval.pages_amount);
+    }
+
+    #[test]
+    fn test_random_read_resets_the_sequence() {
+        let mut state = ReadAheadState::new(ReadAheadSettings::new(8));
+
+        assert!(state.register_read(0, 1).is_none());
+        assert!(state.register_read(1, 1).is_some());
+
+        assert!(state.register_read(10, 1).is_none());
+
+        let interval = state.register_read(11, 1).unwrap();
+        assert_eq!(12, interval.from_page_no);
+        assert_eq!(8, interval.pages_amount);
+    }
+}
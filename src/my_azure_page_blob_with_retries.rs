@@ -1,33 +1,164 @@
-use std::time::Duration;
+use std::{
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
 use my_azure_storage_sdk::{
-    page_blob::{AzurePageBlobStorage, MyAzurePageBlobStorage, PageBlobProperties},
+    page_blob::{
+        consts::BLOB_PAGE_SIZE, AzurePageBlobStorage, MyAzurePageBlobStorage, PageBlobProperties,
+    },
     AzureStorageError,
 };
+use my_telemetry::MyTelemetryContext;
 use rust_extensions::SliceOrVec;
 
-pub struct MyAzurePageBlobStorageWithRetries {
-    pub page_blob: AzurePageBlobStorage,
-    pub retries_amount: usize,
-    pub retry_delay: Duration,
+use crate::{
+    BackoffPolicy, PageBlobOperation, PageBlobTelemetryEvent, PageBlobTimeouts, RecoveryAction,
+    RetryClassifier, RetryPolicy, SingleFlightReads,
+};
+
+pub struct MyAzurePageBlobStorageWithRetries<
+    TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static = AzurePageBlobStorage,
+> {
+    pub page_blob: TMyAzurePageBlobStorage,
+    pub retry_policy: RetryPolicy,
+    pub timeouts: PageBlobTimeouts,
+    pub telemetry: Option<MyTelemetryContext>,
+    single_flight_reads: SingleFlightReads,
+    write_generation: AtomicU64,
 }
 
-impl MyAzurePageBlobStorageWithRetries {
+impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static>
+    MyAzurePageBlobStorageWithRetries<TMyAzurePageBlobStorage>
+{
     pub fn new(
-        page_blob: AzurePageBlobStorage,
+        page_blob: TMyAzurePageBlobStorage,
         retries_amount: usize,
         retry_delay: Duration,
+    ) -> Self {
+        Self::with_backoff(page_blob, retries_amount, BackoffPolicy::fixed(retry_delay))
+    }
+
+    pub fn with_backoff(
+        page_blob: TMyAzurePageBlobStorage,
+        retries_amount: usize,
+        backoff: BackoffPolicy,
+    ) -> Self {
+        Self::with_retry_policy(page_blob, RetryPolicy::new(retries_amount, backoff))
+    }
+
+    pub fn with_retry_policy(
+        page_blob: TMyAzurePageBlobStorage,
+        retry_policy: RetryPolicy,
     ) -> Self {
         Self {
             page_blob,
-            retries_amount,
-            retry_delay,
+            retry_policy,
+            timeouts: PageBlobTimeouts::new(),
+            telemetry: None,
+            single_flight_reads: SingleFlightReads::new(),
+            write_generation: AtomicU64::new(0),
         }
     }
+
+    pub fn with_timeouts(mut self, timeouts: PageBlobTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub fn with_retry_classifier(
+        mut self,
+        retry_classifier: impl RetryClassifier + 'static,
+    ) -> Self {
+        self.retry_policy = self.retry_policy.with_classifier(retry_classifier);
+        self
+    }
+
+    pub fn with_telemetry(mut self, telemetry: MyTelemetryContext) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    async fn execute<TResult, TFuture>(
+        &self,
+        operation: PageBlobOperation,
+        action: impl FnMut() -> TFuture,
+    ) -> Result<TResult, AzureStorageError>
+    where
+        TFuture: Future<Output = Result<TResult, AzureStorageError>>,
+    {
+        let event = PageBlobTelemetryEvent::new(operation.clone());
+        self.execute_with_event(operation, event, action).await
+    }
+
+    async fn execute_with_event<TResult, TFuture>(
+        &self,
+        operation: PageBlobOperation,
+        mut event: PageBlobTelemetryEvent,
+        mut action: impl FnMut() -> TFuture,
+    ) -> Result<TResult, AzureStorageError>
+    where
+        TFuture: Future<Output = Result<TResult, AzureStorageError>>,
+    {
+        let timeout = self.timeouts.get_timeout(&operation);
+        let mut attempts = 0;
+
+        let result = crate::retry_async_with_recovery(
+            &self.retry_policy,
+            operation.clone(),
+            || {
+                attempts += 1;
+                crate::with_timeout(timeout, action())
+            },
+            |action| self.recover(action),
+        )
+        .await
+        .map_err(|err| err.error);
+
+        // Even a failed write could have changed the blob, so reads started after it must not join older downloads
+        if changes_blob(&operation) {
+            self.write_generation.fetch_add(1, Ordering::SeqCst);
+        }
+
+        event.attempts = attempts;
+
+        crate::write_telemetry_event(
+            self.telemetry.as_ref(),
+            self.page_blob.get_container_name(),
+            self.page_blob.get_blob_name(),
+            event,
+            result.as_ref().err(),
+        )
+        .await;
+
+        result
+    }
+
+    async fn recover(&self, action: RecoveryAction) -> Result<(), AzureStorageError> {
+        match action {
+            RecoveryAction::CreateContainerIfNotExists => {
+                self.page_blob.create_container_if_not_exists().await
+            }
+        }
+    }
+}
+
+fn changes_blob(operation: &PageBlobOperation) -> bool {
+    matches!(
+        operation,
+        PageBlobOperation::Resize
+            | PageBlobOperation::Create
+            | PageBlobOperation::CreateIfNotExists
+            | PageBlobOperation::SavePages
+            | PageBlobOperation::Delete
+    )
 }
 
 #[async_trait::async_trait]
-impl MyAzurePageBlobStorage for MyAzurePageBlobStorageWithRetries {
+impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static> MyAzurePageBlobStorage
+    for MyAzurePageBlobStorageWithRetries<TMyAzurePageBlobStorage>
+{
     fn get_blob_name(&self) -> &str {
         self.page_blob.get_blob_name()
     }
@@ -37,113 +168,61 @@ impl MyAzurePageBlobStorage for MyAzurePageBlobStorageWithRetries {
     }
 
     async fn resize(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
-        let mut attempt_no = 0;
-
-        loop {
-            match self.page_blob.resize(pages_amount).await {
-                Ok(result) => {
-                    return Ok(result);
-                }
-                Err(err) => {
-                    if attempt_no >= self.retries_amount {
-                        return Err(err);
-                    }
-                    attempt_no += 1;
-
-                    tokio::time::sleep(self.retry_delay).await;
-                }
-            }
-        }
+        self.execute(PageBlobOperation::Resize, || {
+            self.page_blob.resize(pages_amount)
+        })
+        .await
     }
 
     async fn create_container_if_not_exists(&self) -> Result<(), AzureStorageError> {
-        let mut attempt_no = 0;
-
-        loop {
-            match self.page_blob.create_container_if_not_exists().await {
-                Ok(result) => {
-                    return Ok(result);
-                }
-                Err(err) => {
-                    if attempt_no >= self.retries_amount {
-                        return Err(err);
-                    }
-                    attempt_no += 1;
-
-                    tokio::time::sleep(self.retry_delay).await;
-                }
-            }
-        }
+        self.execute(PageBlobOperation::CreateContainerIfNotExists, || {
+            self.page_blob.create_container_if_not_exists()
+        })
+        .await
     }
 
     async fn create(&self, pages_amount: usize) -> Result<(), AzureStorageError> {
-        let mut attempt_no = 0;
-
-        loop {
-            match self.page_blob.create(pages_amount).await {
-                Ok(result) => {
-                    return Ok(result);
-                }
-                Err(err) => {
-                    if attempt_no >= self.retries_amount {
-                        return Err(err);
-                    }
-                    attempt_no += 1;
-
-                    tokio::time::sleep(self.retry_delay).await;
-                }
-            }
-        }
+        self.execute(PageBlobOperation::Create, || {
+            self.page_blob.create(pages_amount)
+        })
+        .await
     }
+
     async fn create_if_not_exists(
         &self,
         pages_amount: usize,
         auto_create_container: bool,
     ) -> Result<PageBlobProperties, AzureStorageError> {
-        let mut attempt_no = 0;
-
-        loop {
-            match self
-                .page_blob
+        self.execute(PageBlobOperation::CreateIfNotExists, || {
+            self.page_blob
                 .create_if_not_exists(pages_amount, auto_create_container)
-                .await
-            {
-                Ok(result) => {
-                    return Ok(result);
-                }
-                Err(err) => {
-                    if attempt_no >= self.retries_amount {
-                        return Err(err);
-                    }
-                    attempt_no += 1;
-
-                    tokio::time::sleep(self.retry_delay).await;
-                }
-            }
-        }
+        })
+        .await
     }
+
     async fn get_pages(
         &self,
         start_page_no: usize,
         pages_amount: usize,
     ) -> Result<Vec<u8>, AzureStorageError> {
-        let mut attempt_no = 0;
-
-        loop {
-            match self.page_blob.get_pages(start_page_no, pages_amount).await {
-                Ok(result) => {
-                    return Ok(result);
-                }
-                Err(err) => {
-                    if attempt_no >= self.retries_amount {
-                        return Err(err);
-                    }
-                    attempt_no += 1;
-
-                    tokio::time::sleep(self.retry_delay).await;
-                }
-            }
-        }
+        // Concurrent reads of the same pages share one download with all its retries
+        let write_generation = self.write_generation.load(Ordering::SeqCst);
+
+        self.single_flight_reads
+            .get_pages(
+                start_page_no,
+                pages_amount,
+                write_generation,
+                |from_page_no, pages_amount| {
+                    let event = PageBlobTelemetryEvent::new(PageBlobOperation::GetPages)
+                        .with_pages(from_page_no, pages_amount);
+
+                    self.execute_with_event(PageBlobOperation::GetPages, event, move || {
+                        self.page_blob.get_pages(from_page_no, pages_amount)
+                    })
+                },
+            )
+            .await
     }
 
     async fn save_pages<'s>(
@@ -151,86 +230,118 @@ impl MyAzurePageBlobStorage for MyAzurePageBlobStorageWithRetries {
         start_page_no: usize,
         payload: impl Into<SliceOrVec<'s, u8>> + Send + Sync + 'static,
     ) -> Result<(), AzureStorageError> {
-        let mut attempt_no = 0;
-
         let payload: SliceOrVec<'s, u8> = payload.into();
 
-        let payload = payload.as_slice();
-
-        loop {
-            match self.page_blob.save_pages(start_page_no, payload).await {
-                Ok(result) => {
-                    return Ok(result);
-                }
-                Err(err) => {
-                    if attempt_no >= self.retries_amount {
-                        return Err(err);
-                    }
-                    attempt_no += 1;
-
-                    tokio::time::sleep(self.retry_delay).await;
-                }
-            }
+        let chunks = crate::utils::split_into_pages_chunks(
+            start_page_no,
+            payload.as_slice(),
+            BLOB_PAGE_SIZE,
+            crate::utils::MAX_PUT_PAGES_SIZE,
+        );
+
+        for chunk in chunks {
+            let event = PageBlobTelemetryEvent::new(PageBlobOperation::SavePages)
+                .with_pages(chunk.start_page_no, chunk.get_pages_amount(BLOB_PAGE_SIZE));
+
+            self.execute_with_event(PageBlobOperation::SavePages, event, || {
+                self.page_blob
+                    .save_pages(chunk.start_page_no, chunk.payload.to_vec())
+            })
+            .await?;
         }
+
+        Ok(())
     }
 
     async fn delete(&self) -> Result<(), AzureStorageError> {
-        let mut attempt_no = 0;
-
-        loop {
-            match self.page_blob.delete().await {
-                Ok(result) => {
-                    return Ok(result);
-                }
-                Err(err) => {
-                    if attempt_no >= self.retries_amount {
-                        return Err(err);
-                    }
-                    attempt_no += 1;
-
-                    tokio::time::sleep(self.retry_delay).await;
-                }
-            }
-        }
+        self.execute(PageBlobOperation::Delete, || self.page_blob.delete())
+            .await
     }
 
     async fn download(&self) -> Result<Vec<u8>, AzureStorageError> {
-        let mut attempt_no = 0;
-
-        loop {
-            match self.page_blob.download().await {
-                Ok(result) => {
-                    return Ok(result);
-                }
-                Err(err) => {
-                    if attempt_no >= self.retries_amount {
-                        return Err(err);
-                    }
-                    attempt_no += 1;
-
-                    tokio::time::sleep(self.retry_delay).await;
-                }
-            }
-        }
+        self.execute(PageBlobOperation::Download, || self.page_blob.download())
+            .await
     }
 
     async fn get_blob_properties(&self) -> Result<PageBlobProperties, AzureStorageError> {
-        let mut attempt_no = 0;
-
-        loop {
-            match self.page_blob.get_blob_properties().await {
-                Ok(result) => {
-                    return Ok(result.into());
-                }
-                Err(err) => {
-                    if attempt_no >= self.retries_amount {
-                        return Err(err);
-                    }
-                    attempt_no += 1;
-
-                    tokio::time::sleep(self.retry_delay).await;
-                }
-            }
+        self.execute(PageBlobOperation::GetBlobProperties, || {
+            self.page_blob.get_blob_properties()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use my_azure_storage_sdk::{
+        page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage},
+        AzureStorageError,
+    };
+
+    use super::MyAzurePageBlobStorageWithRetries;
+    use crate::{FaultInjectingPageBlob, InMemoryPageBlob};
+
+    #[tokio::test]
+    async fn test_big_payload_is_saved() {
+        let page_blob = MyAzurePageBlobStorageWithRetries::new(
+            InMemoryPageBlob::new("container", "blob"),
+            3,
+            Duration::ZERO,
+        );
+
+        let pages_amount = crate::utils::MAX_PUT_PAGES_SIZE / BLOB_PAGE_SIZE * 2 + 1;
+
+        page_blob
+            .create_if_not_exists(pages_amount, true)
+            .await
+            .unwrap();
+
+        let payload: Vec<u8> = (0..pages_amount * BLOB_PAGE_SIZE)
+            .map(|i| (i / BLOB_PAGE_SIZE) as u8)
+            .collect();
+
+        page_blob.save_pages(0, payload.clone()).await.unwrap();
+
+        assert_eq!(payload, page_blob.download().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_permanent_error_is_returned() {
+        let page_blob = MyAzurePageBlobStorageWithRetries::new(
+            InMemoryPageBlob::new("container", "blob"),
+            3,
+            Duration::from_secs(60),
+        );
+
+        let result = page_blob.get_pages(0, 1).await;
+
+        assert!(matches!(result, Err(AzureStorageError::ContainerNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_reads_share_one_download() {
+        let inner = InMemoryPageBlob::new("container", "blob");
+        inner.create_if_not_exists(4, true).await.unwrap();
+
+        let page_blob = Arc::new(MyAzurePageBlobStorageWithRetries::new(
+            FaultInjectingPageBlob::new(inner, 0).with_latency(Duration::from_millis(100)),
+            3,
+            Duration::ZERO,
+        ));
+
+        let mut tasks = Vec::new();
+
+        for _ in 0..4 {
+            let page_blob = page_blob.clone();
+            tasks.push(tokio::spawn(async move { page_blob.get_pages(1, 2).await }));
+        }
+
+        for task in tasks {
+            assert_eq!(vec![0u8; BLOB_PAGE_SIZE * 2], task.await.unwrap().unwrap());
         }
+
+        assert_eq!(1, page_blob.page_blob.get_calls_amount());
     }
 }
@@ -0,0 +1,310 @@
+use my_azure_storage_sdk::{
+    page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage},
+    AzureStorageError,
+};
+use tokio::sync::Mutex;
+
+/// Reads and writes bytes at arbitrary offsets. Pages which are written partially are read first
+/// and written back with the new bytes on top. The blob grows when a write goes beyond its end.
+///
+/// Writes are serialized and the blob size is remembered after the first write,
+/// so the wrapper expects to be the only writer of the blob.
+pub struct PageBlobByteAccess<
+    TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static,
+> {
+    page_blob: TMyAzurePageBlobStorage,
+    resize_pages_rate: usize,
+    pages_amount: Mutex<Option<usize>>,
+}
+
+impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static>
+    PageBlobByteAccess<TMyAzurePageBlobStorage>
+{
+    pub fn new(page_blob: TMyAzurePageBlobStorage) -> Self {
+        Self {
+            page_blob,
+            resize_pages_rate: 1,
+            pages_amount: Mutex::new(None),
+        }
+    }
+
+    /// The blob grows by the amount of pages which is a multiple of resize_pages_rate
+    pub fn with_resize_pages_rate(mut self, resize_pages_rate: usize) -> Self {
+        self.resize_pages_rate = resize_pages_rate.max(1);
+        self
+    }
+
+    pub fn get_page_blob(&self) -> &TMyAzurePageBlobStorage {
+        &self.page_blob
+    }
+
+    pub async fn read_at(&self, offset: usize, len: usize) -> Result<Vec<u8>, AzureStorageError> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+
+        let start_page_no = offset / BLOB_PAGE_SIZE;
+        let pages_amount = get_end_page_no(offset + len) - start_page_no;
+
+        let mut result = self
+            .page_blob
+            .get_pages(start_page_no, pages_amount)
+            .await?;
+
+        let offset_in_page = offset - start_page_no * BLOB_PAGE_SIZE;
+
+        if offset_in_page + len > result.len() {
+            return Err(AzureStorageError::InvalidPageRange);
+        }
+
+        result.truncate(offset_in_page + len);
+        result.drain(..offset_in_page);
+
+        Ok(result)
+    }
+
+    pub async fn write_at(&self, offset: usize, data: &[u8]) -> Result<(), AzureStorageError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let mut pages_amount = self.pages_amount.lock().await;
+
+        let end_page_no = get_end_page_no(offset + data.len());
+
+        let blob_pages_amount = match *pages_amount {
+            Some(blob_pages_amount) => blob_pages_amount,
+            None => {
+                let properties = self.page_blob.get_blob_properties().await?;
+                properties.blob_properties.blob_size / BLOB_PAGE_SIZE
+            }
+        };
+
+        let blob_pages_amount = if blob_pages_amount < end_page_no {
+            let new_pages_amount = crate::utils::calc_pages_amount_to_ressize(
+                offset + data.len(),
+                BLOB_PAGE_SIZE,
+                self.resize_pages_rate,
+            );
+            self.page_blob.resize(new_pages_amount).await?;
+            new_pages_amount
+        } else {
+            blob_pages_amount
+        };
+
+        *pages_amount = Some(blob_pages_amount);
+
+        let start_page_no = offset / BLOB_PAGE_SIZE;
+        let payload = self.get_payload_to_write(offset, data).await?;
+
+        self.page_blob.save_pages(start_page_no, payload).await
+    }
+
+    /// Builds page aligned payload. Edge pages which are written partially are read from the blob
+    async fn get_payload_to_write(
+        &self,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<Vec<u8>, AzureStorageError> {
+        let start_page_no = offset / BLOB_PAGE_SIZE;
+        let end_page_no = get_end_page_no(offset + data.len());
+
+        let offset_in_page = offset - start_page_no * BLOB_PAGE_SIZE;
+        let end_offset_in_page = (offset + data.len()) % BLOB_PAGE_SIZE;
+
+        if offset_in_page == 0 && end_offset_in_page == 0 {
+            return Ok(data.to_vec());
+        }
+
+        let pages_amount = end_page_no - start_page_no;
+
+        let mut payload = if pages_amount <= 2 {
+            // Both edges are within one request
+            self.page_blob
+                .get_pages(start_page_no, pages_amount)
+                .await?
+        } else {
+            let mut payload = vec![0u8; pages_amount * BLOB_PAGE_SIZE];
+
+            if offset_in_page > 0 {
+                let page = self.page_blob.get_pages(start_page_no, 1).await?;
+                payload[..BLOB_PAGE_SIZE].copy_from_slice(page.as_slice());
+            }
+
+            if end_offset_in_page > 0 {
+                let last_page_no = end_page_no - 1;
+                let page = self.page_blob.get_pages(last_page_no, 1).await?;
+                let payload_offset = (last_page_no - start_page_no) * BLOB_PAGE_SIZE;
+                payload[payload_offset..].copy_from_slice(page.as_slice());
+            }
+
+            payload
+        };
+
+        if payload.len() < offset_in_page + data.len() {
+            return Err(AzureStorageError::InvalidPageRange);
+        }
+
+        payload[offset_in_page..offset_in_page + data.len()].copy_from_slice(data);
+
+        Ok(payload)
+    }
+}
+
+fn get_end_page_no(end_offset: usize) -> usize {
+    crate::utils::get_pages_amount_by_size(end_offset, BLOB_PAGE_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use my_azure_storage_sdk::page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage};
+
+    use super::PageBlobByteAccess;
+    use crate::{InMemoryPageBlob, MyAzurePageBlobStorageWithRetries};
+
+    async fn create_page_blob(pages_amount: usize) -> InMemoryPageBlob {
+        let page_blob = InMemoryPageBlob::new("container", "blob");
+        page_blob
+            .create_if_not_exists(pages_amount, true)
+            .await
+            .unwrap();
+        page_blob
+    }
+
+    #[tokio::test]
+    async fn test_unaligned_write_keeps_edge_pages() {
+        let page_blob = create_page_blob(3).await;
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE * 3])
+            .await
+            .unwrap();
+
+        let byte_access = PageBlobByteAccess::new(page_blob);
+
+        byte_access
+            .write_at(BLOB_PAGE_SIZE - 10, vec![2u8; 20].as_slice())
+            .await
+            .unwrap();
+
+        let content = byte_access.get_page_blob().download().await.unwrap();
+
+        let mut expected = vec![1u8; BLOB_PAGE_SIZE - 10];
+        expected.extend_from_slice([2u8; 20].as_slice());
+        expected.extend_from_slice([1u8; BLOB_PAGE_SIZE * 2 - 10].as_slice());
+
+        assert_eq!(expected, content);
+
+        let result = byte_access.read_at(BLOB_PAGE_SIZE - 12, 24).await.unwrap();
+
+        let mut expected = vec![1u8; 2];
+        expected.extend_from_slice([2u8; 20].as_slice());
+        expected.extend_from_slice([1u8; 2].as_slice());
+
+        assert_eq!(expected, result);
+    }
+
+    #[tokio::test]
+    async fn test_write_spanning_many_pages_reads_only_edges() {
+        let page_blob = create_page_blob(5).await;
+        page_blob
+            .save_pages(0, vec![1u8; BLOB_PAGE_SIZE * 5])
+            .await
+            .unwrap();
+
+        let byte_access = PageBlobByteAccess::new(page_blob);
+
+        let data = vec![2u8; BLOB_PAGE_SIZE * 3];
+        byte_access.write_at(100, data.as_slice()).await.unwrap();
+
+        let content = byte_access.get_page_blob().download().await.unwrap();
+
+        let mut expected = vec![1u8; 100];
+        expected.extend_from_slice(data.as_slice());
+        expected.extend_from_slice([1u8; BLOB_PAGE_SIZE * 2 - 100].as_slice());
+
+        assert_eq!(expected, content);
+    }
+
+    #[tokio::test]
+    async fn test_write_beyond_the_end_resizes_the_blob() {
+        let page_blob = create_page_blob(1).await;
+
+        let byte_access = PageBlobByteAccess::new(page_blob).with_resize_pages_rate(4);
+
+        byte_access
+            .write_at(BLOB_PAGE_SIZE + 1, [5u8; 3].as_slice())
+            .await
+            .unwrap();
+
+        let properties = byte_access
+            .get_page_blob()
+            .get_blob_properties()
+            .await
+            .unwrap();
+        assert_eq!(BLOB_PAGE_SIZE * 4, properties.blob_properties.blob_size);
+
+        let result = byte_access.read_at(BLOB_PAGE_SIZE, 5).await.unwrap();
+        assert_eq!(vec![0u8, 5, 5, 5, 0], result);
+    }
+
+    #[tokio::test]
+    async fn test_read_beyond_the_end_fails() {
+        let byte_access = PageBlobByteAccess::new(create_page_blob(1).await);
+
+        assert!(byte_access.read_at(BLOB_PAGE_SIZE - 1, 2).await.is_err());
+        assert!(byte_access
+            .read_at(BLOB_PAGE_SIZE, 0)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_works_over_retries_wrapper() {
+        let page_blob = MyAzurePageBlobStorageWithRetries::new(
+            create_page_blob(0).await,
+            3,
+            Duration::from_millis(10),
+        );
+
+        let byte_access = PageBlobByteAccess::new(page_blob);
+
+        byte_access.write_at(10, b"hello".as_slice()).await.unwrap();
+        byte_access.write_at(13, b"p!".as_slice()).await.unwrap();
+
+        assert_eq!(b"help!".to_vec(), byte_access.read_at(10, 5).await.unwrap());
+    }
+
+    #[cfg(feature = "blob_with_cache")]
+    #[tokio::test]
+    async fn test_works_over_cache_wrapper() {
+        let inner = create_page_blob(1).await;
+        let storage = inner.get_storage();
+
+        let byte_access = PageBlobByteAccess::new(crate::MyAzurePageBlobWithCache::new(inner));
+
+        byte_access
+            .write_at(BLOB_PAGE_SIZE * 2 + 7, b"cached".as_slice())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            b"cached".to_vec(),
+            byte_access
+                .read_at(BLOB_PAGE_SIZE * 2 + 7, 6)
+                .await
+                .unwrap()
+        );
+
+        let flush_result = byte_access.get_page_blob().flush().await;
+        assert!(flush_result.failed.is_empty());
+
+        let content = storage.get_blob_content("container", "blob").unwrap();
+        assert_eq!(
+            b"cached".as_slice(),
+            &content[BLOB_PAGE_SIZE * 2 + 7..BLOB_PAGE_SIZE * 2 + 13]
+        );
+    }
+}
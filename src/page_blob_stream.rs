@@ -0,0 +1,402 @@
+use std::{
+    future::Future,
+    io::{Error, ErrorKind, SeekFrom},
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use my_azure_storage_sdk::{
+    page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage},
+    AzureStorageError,
+};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+use crate::PageBlobByteAccess;
+
+pub const DEFAULT_STREAM_CHUNK_SIZE: usize = 1024 * BLOB_PAGE_SIZE;
+
+enum PendingOperation {
+    Idle,
+    Read {
+        position: u64,
+        future: BoxFuture<'static, Result<Vec<u8>, AzureStorageError>>,
+    },
+    Write(BoxFuture<'static, Result<(), AzureStorageError>>),
+}
+
+/// Page blob as a tokio stream of bytes. The stream has its own logical length,
+/// so the content does not have to be page aligned: reads stop at the logical length
+/// and writes beyond it make it longer.
+///
+/// Reads and writes go by chunks of chunk_size bytes. Written bytes are buffered
+/// and only whole pages are uploaded until the stream is flushed, so the last partially written page
+/// is uploaded once. Bytes which are not flushed before the stream is dropped are lost.
+pub struct PageBlobStream<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static> {
+    byte_access: Arc<PageBlobByteAccess<TMyAzurePageBlobStorage>>,
+    len: u64,
+    position: u64,
+    chunk_size: usize,
+    read_buffer: Vec<u8>,
+    read_buffer_position: u64,
+    write_buffer: Vec<u8>,
+    write_buffer_position: u64,
+    pending_operation: PendingOperation,
+}
+
+impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static>
+    PageBlobStream<TMyAzurePageBlobStorage>
+{
+    pub fn new(page_blob: TMyAzurePageBlobStorage, len: u64) -> Self {
+        Self::from_byte_access(Arc::new(PageBlobByteAccess::new(page_blob)), len)
+    }
+
+    pub fn from_byte_access(
+        byte_access: Arc<PageBlobByteAccess<TMyAzurePageBlobStorage>>,
+        len: u64,
+    ) -> Self {
+        Self {
+            byte_access,
+            len,
+            position: 0,
+            chunk_size: DEFAULT_STREAM_CHUNK_SIZE,
+            read_buffer: Vec::new(),
+            read_buffer_position: 0,
+            write_buffer: Vec::new(),
+            write_buffer_position: 0,
+            pending_operation: PendingOperation::Idle,
+        }
+    }
+
+    /// Chunk size is rounded up to whole pages
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = crate::utils::get_pages_amount_by_size(chunk_size.max(1), BLOB_PAGE_SIZE)
+            * BLOB_PAGE_SIZE;
+        self
+    }
+
+    pub fn get_byte_access(&self) -> &PageBlobByteAccess<TMyAzurePageBlobStorage> {
+        self.byte_access.as_ref()
+    }
+
+    /// Logical length of the content including bytes which are not flushed yet
+    pub fn get_len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn get_position(&self) -> u64 {
+        self.position
+    }
+
+    fn poll_pending_operation(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let result = match &mut self.pending_operation {
+            PendingOperation::Idle => return Poll::Ready(Ok(())),
+            PendingOperation::Read { position, future } => {
+                let position = *position;
+                ready!(future.as_mut().poll(cx)).map(|payload| {
+                    self.read_buffer = payload;
+                    self.read_buffer_position = position;
+                })
+            }
+            PendingOperation::Write(future) => ready!(future.as_mut().poll(cx)),
+        };
+
+        self.pending_operation = PendingOperation::Idle;
+
+        Poll::Ready(result.map_err(to_io_error))
+    }
+
+    /// Uploads buffered bytes. Unless everything is uploaded, the partially written last page stays in the buffer
+    fn start_write(&mut self, everything: bool) {
+        let buffer_end = self.write_buffer_position + self.write_buffer.len() as u64;
+
+        let size = if everything {
+            self.write_buffer.len()
+        } else {
+            let aligned_end = buffer_end - buffer_end % BLOB_PAGE_SIZE as u64;
+            aligned_end.saturating_sub(self.write_buffer_position) as usize
+        };
+
+        if size == 0 {
+            return;
+        }
+
+        let payload: Vec<u8> = self.write_buffer.drain(..size).collect();
+        let offset = self.write_buffer_position as usize;
+        self.write_buffer_position += size as u64;
+
+        let byte_access = self.byte_access.clone();
+        self.pending_operation = PendingOperation::Write(Box::pin(async move {
+            byte_access.write_at(offset, payload.as_slice()).await
+        }));
+    }
+
+    fn start_read(&mut self) {
+        let position = self.position;
+        let len = (self.len - position).min(self.chunk_size as u64) as usize;
+
+        let byte_access = self.byte_access.clone();
+        self.pending_operation = PendingOperation::Read {
+            position,
+            future: Box::pin(async move { byte_access.read_at(position as usize, len).await }),
+        };
+    }
+
+    fn get_buffered_content(&self) -> Option<&[u8]> {
+        let buffer_end = self.read_buffer_position + self.read_buffer.len() as u64;
+
+        if self.position < self.read_buffer_position || self.position >= buffer_end {
+            return None;
+        }
+
+        let offset = (self.position - self.read_buffer_position) as usize;
+        Some(&self.read_buffer[offset..])
+    }
+
+    fn poll_flush_write_buffer(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        loop {
+            ready!(self.poll_pending_operation(cx))?;
+
+            if self.write_buffer.is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+
+            self.start_write(true);
+        }
+    }
+}
+
+impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static> AsyncRead
+    for PageBlobStream<TMyAzurePageBlobStorage>
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+
+        // Reads have to see what is written before them
+        ready!(this.poll_flush_write_buffer(cx))?;
+
+        loop {
+            if this.position >= this.len || buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            if let Some(content) = this.get_buffered_content() {
+                let size = content.len().min(buf.remaining());
+                buf.put_slice(&content[..size]);
+                this.position += size as u64;
+                return Poll::Ready(Ok(()));
+            }
+
+            this.start_read();
+            ready!(this.poll_pending_operation(cx))?;
+        }
+    }
+}
+
+impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static> AsyncWrite
+    for PageBlobStream<TMyAzurePageBlobStorage>
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+
+        loop {
+            ready!(this.poll_pending_operation(cx))?;
+
+            let buffer_end = this.write_buffer_position + this.write_buffer.len() as u64;
+
+            if this.write_buffer.is_empty() {
+                this.write_buffer_position = this.position;
+            } else if buffer_end != this.position {
+                // Position was moved by seek, so buffered bytes are not followed by the new ones
+                this.start_write(true);
+                continue;
+            }
+
+            if this.write_buffer.len() < this.chunk_size {
+                break;
+            }
+
+            this.start_write(false);
+        }
+
+        let size = buf.len().min(this.chunk_size - this.write_buffer.len());
+        this.write_buffer.extend_from_slice(&buf[..size]);
+
+        this.position += size as u64;
+        this.len = this.len.max(this.position);
+
+        // Buffered reads can contain previous content of the written bytes
+        this.read_buffer.clear();
+
+        Poll::Ready(Ok(size))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut().poll_flush_write_buffer(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut().poll_flush_write_buffer(cx)
+    }
+}
+
+impl<TMyAzurePageBlobStorage: MyAzurePageBlobStorage + Send + Sync + 'static> AsyncSeek
+    for PageBlobStream<TMyAzurePageBlobStorage>
+{
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> std::io::Result<()> {
+        let this = self.get_mut();
+
+        let new_position = match position {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::End(offset) => this.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => this.position.checked_add_signed(offset),
+        };
+
+        let Some(new_position) = new_position else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Seek to a negative or overflowing position",
+            ));
+        };
+
+        this.position = new_position;
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+        Poll::Ready(Ok(self.position))
+    }
+}
+
+fn to_io_error(err: AzureStorageError) -> Error {
+    Error::other(format!("{:?}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::SeekFrom;
+
+    use my_azure_storage_sdk::page_blob::{consts::BLOB_PAGE_SIZE, MyAzurePageBlobStorage};
+    use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+    use super::PageBlobStream;
+    use crate::InMemoryPageBlob;
+
+    async fn create_page_blob() -> InMemoryPageBlob {
+        let page_blob = InMemoryPageBlob::new("container", "blob");
+        page_blob.create_if_not_exists(0, true).await.unwrap();
+        page_blob
+    }
+
+    fn get_content(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn test_logical_length_is_not_page_aligned() {
+        let mut stream = PageBlobStream::new(create_page_blob().await, 0);
+
+        let content = get_content(1000);
+        stream.write_all(content.as_slice()).await.unwrap();
+        stream.shutdown().await.unwrap();
+
+        assert_eq!(1000, stream.get_len());
+
+        let properties = stream
+            .get_byte_access()
+            .get_page_blob()
+            .get_blob_properties()
+            .await
+            .unwrap();
+        assert_eq!(BLOB_PAGE_SIZE * 2, properties.blob_properties.blob_size);
+
+        stream.seek(SeekFrom::Start(0)).await.unwrap();
+
+        let mut result = Vec::new();
+        stream.read_to_end(&mut result).await.unwrap();
+
+        assert_eq!(content, result);
+    }
+
+    #[tokio::test]
+    async fn test_small_chunks_with_unaligned_writes() {
+        let mut stream = PageBlobStream::new(create_page_blob().await, 0).with_chunk_size(100);
+
+        let content = get_content(BLOB_PAGE_SIZE * 3 + 17);
+
+        for chunk in content.chunks(300) {
+            stream.write_all(chunk).await.unwrap();
+        }
+
+        stream.flush().await.unwrap();
+
+        let stored = stream
+            .get_byte_access()
+            .get_page_blob()
+            .download()
+            .await
+            .unwrap();
+        assert_eq!(content.as_slice(), &stored[..content.len()]);
+
+        stream.rewind().await.unwrap();
+
+        let mut result = Vec::new();
+        stream.read_to_end(&mut result).await.unwrap();
+
+        assert_eq!(content, result);
+    }
+
+    #[tokio::test]
+    async fn test_seek_and_overwrite_in_the_middle() {
+        let mut stream = PageBlobStream::new(create_page_blob().await, 0);
+
+        stream.write_all(vec![1u8; 700].as_slice()).await.unwrap();
+
+        // Buffered bytes are uploaded before the position is moved away from them
+        stream.seek(SeekFrom::Start(500)).await.unwrap();
+        stream.write_all([2u8; 20].as_slice()).await.unwrap();
+        stream.flush().await.unwrap();
+
+        assert_eq!(700, stream.get_len());
+
+        assert_eq!(690, stream.seek(SeekFrom::End(-10)).await.unwrap());
+
+        let mut result = Vec::new();
+        stream.read_to_end(&mut result).await.unwrap();
+        assert_eq!(vec![1u8; 10], result);
+
+        stream.seek(SeekFrom::Start(495)).await.unwrap();
+
+        let mut result = [0u8; 30];
+        stream.read_exact(&mut result).await.unwrap();
+
+        let mut expected = vec![1u8; 5];
+        expected.extend_from_slice([2u8; 20].as_slice());
+        expected.extend_from_slice([1u8; 5].as_slice());
+
+        assert_eq!(expected.as_slice(), result.as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_read_stops_at_logical_length() {
+        let page_blob = create_page_blob().await;
+        page_blob.resize(1).await.unwrap();
+
+        let mut stream = PageBlobStream::new(page_blob, 10);
+
+        let mut result = Vec::new();
+        stream.read_to_end(&mut result).await.unwrap();
+        assert_eq!(vec![0u8; 10], result);
+
+        assert!(stream.seek(SeekFrom::Current(-11)).await.is_err());
+    }
+}
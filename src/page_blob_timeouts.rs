@@ -0,0 +1,123 @@
+use std::{future::Future, time::Duration};
+
+use my_azure_storage_sdk::AzureStorageError;
+
+use crate::PageBlobOperation;
+
+/// Timeouts of a single attempt. None means that the attempt can last forever.
+#[derive(Debug, Clone, Default)]
+pub struct PageBlobTimeouts {
+    /// get_pages
+    pub read: Option<Duration>,
+    /// save_pages
+    pub write: Option<Duration>,
+    /// Blob and container management: create, resize, delete, get_blob_properties
+    pub metadata: Option<Duration>,
+    /// Full blob download
+    pub download: Option<Duration>,
+}
+
+impl PageBlobTimeouts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_read(mut self, timeout: Duration) -> Self {
+        self.read = Some(timeout);
+        self
+    }
+
+    pub fn with_write(mut self, timeout: Duration) -> Self {
+        self.write = Some(timeout);
+        self
+    }
+
+    pub fn with_metadata(mut self, timeout: Duration) -> Self {
+        self.metadata = Some(timeout);
+        self
+    }
+
+    pub fn with_download(mut self, timeout: Duration) -> Self {
+        self.download = Some(timeout);
+        self
+    }
+
+    pub fn get_timeout(&self, operation: &PageBlobOperation) -> Option<Duration> {
+        match operation {
+            PageBlobOperation::GetPages => self.read,
+            PageBlobOperation::SavePages => self.write,
+            PageBlobOperation::Download => self.download,
+            PageBlobOperation::Resize
+            | PageBlobOperation::CreateContainerIfNotExists
+            | PageBlobOperation::Create
+            | PageBlobOperation::CreateIfNotExists
+            | PageBlobOperation::Delete
+            | PageBlobOperation::GetBlobProperties
+            | PageBlobOperation::Other(_) => self.metadata,
+        }
+    }
+}
+
+/// Converts elapsed timeout into AzureStorageError::Timeout, so the retry policy can handle it
+pub async fn with_timeout<TResult>(
+    timeout: Option<Duration>,
+    future: impl Future<Output = Result<TResult, AzureStorageError>>,
+) -> Result<TResult, AzureStorageError> {
+    let Some(timeout) = timeout else {
+        return future.await;
+    };
+
+    match tokio::time::timeout(timeout, future).await {
+        Ok(result) => result,
+        Err(_) => Err(AzureStorageError::Timeout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use my_azure_storage_sdk::AzureStorageError;
+
+    use super::*;
+
+    #[test]
+    fn test_timeouts_are_selected_by_operation() {
+        let timeouts = PageBlobTimeouts::new()
+            .with_read(Duration::from_secs(1))
+            .with_write(Duration::from_secs(2))
+            .with_metadata(Duration::from_secs(3));
+
+        assert_eq!(
+            Some(Duration::from_secs(1)),
+            timeouts.get_timeout(&PageBlobOperation::GetPages)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(2)),
+            timeouts.get_timeout(&PageBlobOperation::SavePages)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(3)),
+            timeouts.get_timeout(&PageBlobOperation::Resize)
+        );
+        assert_eq!(None, timeouts.get_timeout(&PageBlobOperation::Download));
+    }
+
+    #[tokio::test]
+    async fn test_hung_future_is_converted_into_timeout_error() {
+        let result: Result<(), AzureStorageError> = with_timeout(
+            Some(Duration::from_millis(10)),
+            std::future::pending::<Result<(), AzureStorageError>>(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(AzureStorageError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn test_no_timeout_awaits_result() {
+        let result = with_timeout(None, async { Ok::<_, AzureStorageError>(5) }).await;
+
+        assert_eq!(5, result.unwrap());
+    }
+}
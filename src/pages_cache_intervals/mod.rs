@@ -1,7 +1,9 @@
 mod pages_cache_intervals;
 mod pages_cache_item;
 mod pages_merger;
+mod pages_range;
 
 pub use pages_cache_intervals::*;
 pub use pages_cache_item::*;
 pub use pages_merger::*;
+pub use pages_range::*;
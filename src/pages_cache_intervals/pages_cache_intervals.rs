@@ -1,103 +1,185 @@
-use crate::PagesCacheItem;
+use std::collections::BTreeMap;
 
-enum InsertToPagesAction {
-    AtIndex(usize),
-    AtTheEnd,
-    MergeWithPage(usize),
-    MergeWithPages(Vec<usize>),
-}
+use my_azure_storage_sdk::page_blob::consts::BLOB_PAGE_SIZE;
+
+use crate::{PagesCacheItem, PagesRangeIterator};
 
+/// Intervals of pages keyed by their first page. Intervals neither overlap nor touch each other:
+/// adjacent and overlapping writes are merged into one interval
 pub struct PagesCacheIntervals {
-    pub pages: Vec<PagesCacheItem>,
+    pages: BTreeMap<usize, PagesCacheItem>,
+    dirty_size: usize,
 }
 
 impl PagesCacheIntervals {
     pub fn new() -> Self {
-        Self { pages: Vec::new() }
+        Self {
+            pages: BTreeMap::new(),
+            dirty_size: 0,
+        }
     }
 
     pub fn update_pages(&mut self, page_no: usize, content: Vec<u8>) {
         let new_item = PagesCacheItem::new(page_no, content);
-        let action = self.get_page_index_to_merge(&new_item);
 
-        match action {
-            InsertToPagesAction::AtIndex(page_index) => {
-                self.pages.insert(page_index, new_item);
-            }
-            InsertToPagesAction::AtTheEnd => {
-                self.pages.push(new_item);
-            }
-            InsertToPagesAction::MergeWithPage(page_index) => {
-                self.pages.get_mut(page_index).unwrap().merge(new_item);
+        let pages_to_merge = self.get_pages_to_merge(&new_item);
+
+        let merged_page = match pages_to_merge.len() {
+            0 => new_item,
+            1 => {
+                let mut page = self.remove_page(pages_to_merge[0]);
+
+                // Fast path for appends. PagesCacheItem::merge does not keep pages after the new content
+                if new_item.page_id >= page.page_id
+                    && new_item.get_last_page_id() >= page.get_last_page_id()
+                {
+                    page.merge(new_item);
+                    page
+                } else {
+                    super::pages_merger::merge_pages(&[&page], &new_item)
+                }
             }
-            InsertToPagesAction::MergeWithPages(pages) => {
-                let pages_to_merge: Vec<&PagesCacheItem> = pages
-                    .iter()
-                    .map(|page_index| self.pages.get(*page_index).unwrap())
+            _ => {
+                let pages: Vec<PagesCacheItem> = pages_to_merge
+                    .into_iter()
+                    .map(|page_id| self.remove_page(page_id))
                     .collect();
 
-                let merged_page =
-                    super::pages_merger::merge_pages(pages_to_merge.as_slice(), &new_item);
-
-                let start_index = *pages.get(0).unwrap();
+                let pages: Vec<&PagesCacheItem> = pages.iter().collect();
 
-                self.remove_pages(start_index, pages.len());
-
-                self.pages.insert(start_index, merged_page);
+                super::pages_merger::merge_pages(pages.as_slice(), &new_item)
             }
-        }
+        };
+
+        self.dirty_size += merged_page.content.len();
+        self.pages.insert(merged_page.page_id, merged_page);
     }
 
-    fn remove_pages(&mut self, at_index: usize, amount: usize) {
-        self.pages.drain(at_index..at_index + amount);
+    fn remove_page(&mut self, page_id: usize) -> PagesCacheItem {
+        let page = self.pages.remove(&page_id).unwrap();
+        self.dirty_size -= page.content.len();
+        page
     }
-    fn get_page_index_to_merge(&self, new_content: &PagesCacheItem) -> InsertToPagesAction {
-        let mut no = 0;
 
-        let mut found_pages = Vec::with_capacity(self.pages.len());
+    /// Returns first pages of intervals which overlap or touch the new one
+    fn get_pages_to_merge(&self, new_content: &PagesCacheItem) -> Vec<usize> {
+        let mut result = Vec::new();
 
-        for page in &self.pages {
-            match page.is_my_page_to_merge(&new_content) {
-                crate::IsMyPageToAddResult::Before => {
-                    if found_pages.len() == 0 {
-                        return InsertToPagesAction::AtIndex(no);
-                    }
-                    break;
-                }
-                crate::IsMyPageToAddResult::Here => {
-                    found_pages.push(no);
-                }
-                crate::IsMyPageToAddResult::After => {}
+        // Only the interval which starts before the new one can reach it, since intervals do not overlap
+        if let Some((page_id, page)) = self.pages.range(..new_content.page_id).next_back() {
+            if page.is_my_page_to_merge(new_content).is_here() {
+                result.push(*page_id);
             }
-            no += 1;
         }
 
-        match found_pages.len() {
-            0 => {
-                return InsertToPagesAction::AtTheEnd;
-            }
-            1 => {
-                return InsertToPagesAction::MergeWithPage(*found_pages.get(0).unwrap());
-            }
-            _ => {
-                return InsertToPagesAction::MergeWithPages(found_pages);
-            }
+        for page_id in self
+            .pages
+            .range(new_content.page_id..=new_content.get_last_page_id())
+            .map(|(page_id, _)| *page_id)
+        {
+            result.push(page_id);
         }
+
+        result
     }
 
     pub fn get_page(&self, page_id: usize) -> Option<&[u8]> {
-        for page in &self.pages {
-            if let Some(page_content) = page.get_content(page_id) {
-                return Some(page_content);
+        let (_, page) = self.pages.range(..=page_id).next_back()?;
+        page.get_content(page_id)
+    }
+
+    /// Splits the range into sub-ranges which are covered by intervals and gaps between them
+    pub fn get_range(&self, start_page_no: usize, pages_amount: usize) -> PagesRangeIterator<'_> {
+        // The interval which starts before the range can cover its beginning
+        let from_page_no = match self.pages.range(..start_page_no).next_back() {
+            Some((page_id, page)) if page.get_last_page_id() > start_page_no => *page_id,
+            _ => start_page_no,
+        };
+
+        PagesRangeIterator::new(
+            self.pages.range(from_page_no..start_page_no + pages_amount),
+            start_page_no,
+            pages_amount,
+        )
+    }
+
+    /// Intervals ordered by their first page
+    pub fn iter(&self) -> impl Iterator<Item = &PagesCacheItem> {
+        self.pages.values()
+    }
+
+    /// Intervals ordered by their first page. Replaces the former public `pages` field; prefer `iter` to avoid the allocation
+    pub fn get_items(&self) -> Vec<&PagesCacheItem> {
+        self.pages.values().collect()
+    }
+
+    pub fn get_intervals_amount(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    pub fn get_dirty_size(&self) -> usize {
+        self.dirty_size
+    }
+
+    pub fn take_all(&mut self) -> Vec<PagesCacheItem> {
+        self.dirty_size = 0;
+        std::mem::take(&mut self.pages).into_values().collect()
+    }
+
+    /// Removes the interval if it is still the same as the given one. Returns false if it was changed or merged meanwhile
+    pub fn remove_if_unchanged(&mut self, item: &PagesCacheItem) -> bool {
+        let is_unchanged = self
+            .pages
+            .get(&item.page_id)
+            .is_some_and(|page| page.content == item.content);
+
+        if is_unchanged {
+            self.remove_page(item.page_id);
+        }
+
+        is_unchanged
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.dirty_size = 0;
+    }
+
+    pub fn has_pages_from(&self, page_no: usize) -> bool {
+        self.pages
+            .values()
+            .next_back()
+            .is_some_and(|page| page.get_last_page_id() > page_no)
+    }
+
+    /// Drops everything starting from page_no
+    pub fn truncate(&mut self, page_no: usize) {
+        for page in self.pages.split_off(&page_no).into_values() {
+            self.dirty_size -= page.content.len();
+        }
+
+        if let Some(last_page) = self.pages.values_mut().next_back() {
+            if last_page.get_last_page_id() > page_no {
+                let size = (page_no - last_page.page_id) * BLOB_PAGE_SIZE;
+                self.dirty_size -= last_page.content.len() - size;
+                last_page.content.truncate(size);
             }
         }
-        None
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::PagesRange;
+
+    fn get_items(pages_cache: &PagesCacheIntervals) -> Vec<&PagesCacheItem> {
+        pages_cache.iter().collect()
+    }
 
     #[test]
     fn test_we_merge_page_exactly_before() {
@@ -105,20 +187,20 @@ mod tests {
 
         pages_cache.update_pages(2, vec![0u8; 512]);
 
-        assert_eq!(1, pages_cache.pages.len());
+        assert_eq!(1, pages_cache.get_intervals_amount());
 
-        assert_eq!([0u8; 512].as_slice(), pages_cache.pages[0].content);
-        assert_eq!(2, pages_cache.pages[0].page_id);
+        assert_eq!([0u8; 512].as_slice(), get_items(&pages_cache)[0].content);
+        assert_eq!(2, get_items(&pages_cache)[0].page_id);
 
         pages_cache.update_pages(1, vec![1u8; 512]);
 
-        assert_eq!(1, pages_cache.pages.len());
+        assert_eq!(1, pages_cache.get_intervals_amount());
 
         let mut result = vec![1u8; 512];
         result.extend_from_slice([0u8; 512].as_slice());
 
-        assert_eq!(result, pages_cache.pages[0].content);
-        assert_eq!(1, pages_cache.pages[0].page_id);
+        assert_eq!(result, get_items(&pages_cache)[0].content);
+        assert_eq!(1, get_items(&pages_cache)[0].page_id);
     }
 
     #[test]
@@ -127,20 +209,20 @@ mod tests {
 
         pages_cache.update_pages(1, vec![0u8; 512]);
 
-        assert_eq!(1, pages_cache.pages.len());
+        assert_eq!(1, pages_cache.get_intervals_amount());
 
-        assert_eq!([0u8; 512].as_slice(), pages_cache.pages[0].content);
-        assert_eq!(1, pages_cache.pages[0].page_id);
+        assert_eq!([0u8; 512].as_slice(), get_items(&pages_cache)[0].content);
+        assert_eq!(1, get_items(&pages_cache)[0].page_id);
 
         pages_cache.update_pages(2, vec![1u8; 512]);
 
-        assert_eq!(1, pages_cache.pages.len());
+        assert_eq!(1, pages_cache.get_intervals_amount());
 
         let mut result = vec![0u8; 512];
         result.extend_from_slice([1u8; 512].as_slice());
 
-        assert_eq!(result, pages_cache.pages[0].content);
-        assert_eq!(1, pages_cache.pages[0].page_id);
+        assert_eq!(result, get_items(&pages_cache)[0].content);
+        assert_eq!(1, get_items(&pages_cache)[0].page_id);
     }
 
     #[test]
@@ -150,7 +232,7 @@ mod tests {
         pages_cache.update_pages(1, vec![0u8; 512]);
         pages_cache.update_pages(3, vec![2u8; 512]);
 
-        assert_eq!(2, pages_cache.pages.len());
+        assert_eq!(2, pages_cache.get_intervals_amount());
 
         pages_cache.update_pages(2, vec![1u8; 512]);
 
@@ -158,9 +240,9 @@ mod tests {
         result.extend_from_slice([1u8; 512].as_slice());
         result.extend_from_slice([2u8; 512].as_slice());
 
-        assert_eq!(1, pages_cache.pages.len());
-        assert_eq!(1, pages_cache.pages[0].page_id);
-        assert_eq!(result, pages_cache.pages[0].content);
+        assert_eq!(1, pages_cache.get_intervals_amount());
+        assert_eq!(1, get_items(&pages_cache)[0].page_id);
+        assert_eq!(result, get_items(&pages_cache)[0].content);
     }
 
     #[test]
@@ -170,7 +252,7 @@ mod tests {
         pages_cache.update_pages(1, vec![0u8; 1024]);
         pages_cache.update_pages(4, vec![2u8; 512]);
 
-        assert_eq!(2, pages_cache.pages.len());
+        assert_eq!(2, pages_cache.get_intervals_amount());
 
         pages_cache.update_pages(2, vec![1u8; 1024]);
 
@@ -178,9 +260,9 @@ mod tests {
         result.extend_from_slice([1u8; 1024].as_slice());
         result.extend_from_slice([2u8; 512].as_slice());
 
-        assert_eq!(1, pages_cache.pages.len());
-        assert_eq!(1, pages_cache.pages[0].page_id);
-        assert_eq!(result, pages_cache.pages[0].content);
+        assert_eq!(1, pages_cache.get_intervals_amount());
+        assert_eq!(1, get_items(&pages_cache)[0].page_id);
+        assert_eq!(result, get_items(&pages_cache)[0].content);
     }
 
     #[test]
@@ -190,7 +272,7 @@ mod tests {
         pages_cache.update_pages(1, vec![0u8; 1024]);
         pages_cache.update_pages(4, vec![2u8; 1024]);
 
-        assert_eq!(2, pages_cache.pages.len());
+        assert_eq!(2, pages_cache.get_intervals_amount());
 
         pages_cache.update_pages(2, vec![1u8; 512 * 3]);
 
@@ -198,9 +280,9 @@ mod tests {
         result.extend_from_slice([1u8; 512 * 3].as_slice());
         result.extend_from_slice([2u8; 512].as_slice());
 
-        assert_eq!(1, pages_cache.pages.len());
-        assert_eq!(1, pages_cache.pages[0].page_id);
-        assert_eq!(result, pages_cache.pages[0].content);
+        assert_eq!(1, pages_cache.get_intervals_amount());
+        assert_eq!(1, get_items(&pages_cache)[0].page_id);
+        assert_eq!(result, get_items(&pages_cache)[0].content);
     }
 
     #[test]
@@ -211,7 +293,7 @@ mod tests {
         pages_cache.update_pages(4, vec![2u8; 1024]);
         pages_cache.update_pages(7, vec![3u8; 1024]);
 
-        assert_eq!(3, pages_cache.pages.len());
+        assert_eq!(3, pages_cache.get_intervals_amount());
 
         pages_cache.update_pages(2, vec![4u8; 512 * 6]);
 
@@ -219,9 +301,9 @@ mod tests {
         result.extend_from_slice([4u8; 512 * 6].as_slice());
         result.extend_from_slice([3u8; 512].as_slice());
 
-        assert_eq!(1, pages_cache.pages.len());
-        assert_eq!(1, pages_cache.pages[0].page_id);
-        assert_eq!(result, pages_cache.pages[0].content);
+        assert_eq!(1, pages_cache.get_intervals_amount());
+        assert_eq!(1, get_items(&pages_cache)[0].page_id);
+        assert_eq!(result, get_items(&pages_cache)[0].content);
     }
 
     #[test]
@@ -231,15 +313,15 @@ mod tests {
         pages_cache.update_pages(1, vec![0u8; 512]);
         pages_cache.update_pages(5, vec![2u8; 512]);
 
-        assert_eq!(2, pages_cache.pages.len());
+        assert_eq!(2, pages_cache.get_intervals_amount());
 
         pages_cache.update_pages(3, vec![1u8; 512]);
 
-        assert_eq!(3, pages_cache.pages.len());
+        assert_eq!(3, pages_cache.get_intervals_amount());
 
-        assert_eq!(1, pages_cache.pages[0].page_id);
-        assert_eq!(3, pages_cache.pages[1].page_id);
-        assert_eq!(5, pages_cache.pages[2].page_id);
+        assert_eq!(1, get_items(&pages_cache)[0].page_id);
+        assert_eq!(3, get_items(&pages_cache)[1].page_id);
+        assert_eq!(5, get_items(&pages_cache)[2].page_id);
     }
 
     #[test]
@@ -256,4 +338,189 @@ mod tests {
         assert_eq!(pages_cache.get_page(5).unwrap(), [2u8; 512].as_slice());
         assert!(pages_cache.get_page(6).is_none());
     }
+
+    #[test]
+    fn test_take_all_drains_intervals() {
+        let mut pages_cache = PagesCacheIntervals::new();
+        pages_cache.update_pages(1, vec![1u8; 1024]);
+        pages_cache.update_pages(5, vec![2u8; 512]);
+
+        assert_eq!(1536, pages_cache.get_dirty_size());
+
+        let taken = pages_cache.take_all();
+
+        assert_eq!(2, taken.len());
+        assert!(pages_cache.is_empty());
+        assert_eq!(0, pages_cache.get_dirty_size());
+    }
+
+    #[test]
+    fn test_remove_if_unchanged() {
+        let mut pages_cache = PagesCacheIntervals::new();
+        pages_cache.update_pages(1, vec![1u8; 1024]);
+        pages_cache.update_pages(5, vec![2u8; 512]);
+
+        let snapshot: Vec<PagesCacheItem> = pages_cache.iter().cloned().collect();
+
+        pages_cache.update_pages(6, vec![3u8; 512]);
+
+        assert!(pages_cache.remove_if_unchanged(&snapshot[0]));
+        assert!(!pages_cache.remove_if_unchanged(&snapshot[1]));
+
+        assert_eq!(1, pages_cache.get_intervals_amount());
+        assert_eq!(5, get_items(&pages_cache)[0].page_id);
+        assert_eq!(1024, pages_cache.get_dirty_size());
+    }
+
+    #[test]
+    fn test_truncate_drops_and_cuts_intervals() {
+        let mut pages_cache = PagesCacheIntervals::new();
+        pages_cache.update_pages(1, vec![1u8; 512 * 3]);
+        pages_cache.update_pages(6, vec![2u8; 512]);
+
+        assert!(pages_cache.has_pages_from(6));
+
+        pages_cache.truncate(3);
+
+        assert!(!pages_cache.has_pages_from(3));
+        assert_eq!(1, pages_cache.get_intervals_amount());
+        assert_eq!(1, get_items(&pages_cache)[0].page_id);
+        assert_eq!(vec![1u8; 1024], get_items(&pages_cache)[0].content);
+    }
+
+    #[test]
+    fn test_truncate_on_interval_border() {
+        let mut pages_cache = PagesCacheIntervals::new();
+        pages_cache.update_pages(1, vec![1u8; 1024]);
+
+        pages_cache.truncate(1);
+
+        assert!(pages_cache.is_empty());
+    }
+
+    #[test]
+    fn test_dirty_size_follows_merges() {
+        let mut pages_cache = PagesCacheIntervals::new();
+
+        pages_cache.update_pages(1, vec![1u8; 1024]);
+        pages_cache.update_pages(5, vec![2u8; 512]);
+        pages_cache.update_pages(2, vec![3u8; 512 * 3]);
+
+        assert_eq!(1, pages_cache.get_intervals_amount());
+        assert_eq!(512 * 5, pages_cache.get_dirty_size());
+
+        pages_cache.truncate(4);
+        assert_eq!(512 * 3, pages_cache.get_dirty_size());
+    }
+
+    #[test]
+    fn test_many_scattered_intervals() {
+        let mut pages_cache = PagesCacheIntervals::new();
+
+        for page_no in (0..1000).rev() {
+            pages_cache.update_pages(page_no * 2, vec![(page_no % 256) as u8; 512]);
+        }
+
+        assert_eq!(1000, pages_cache.get_intervals_amount());
+        assert_eq!([7u8; 512].as_slice(), pages_cache.get_page(14).unwrap());
+        assert!(pages_cache.get_page(15).is_none());
+
+        // Filling the gaps merges everything into one interval
+        for page_no in 0..999 {
+            pages_cache.update_pages(page_no * 2 + 1, vec![0u8; 512]);
+        }
+
+        assert_eq!(1, pages_cache.get_intervals_amount());
+        assert_eq!(512 * 1999, pages_cache.get_dirty_size());
+    }
+
+    #[test]
+    fn test_get_range() {
+        let mut pages_cache = PagesCacheIntervals::new();
+        pages_cache.update_pages(1, vec![1u8; 1024]);
+        pages_cache.update_pages(5, vec![2u8; 512 * 3]);
+
+        let result: Vec<PagesRange> = pages_cache.get_range(0, 10).collect();
+
+        assert_eq!(
+            vec![
+                PagesRange::Uncovered {
+                    from_page_no: 0,
+                    amount: 1
+                },
+                PagesRange::Covered {
+                    from_page_no: 1,
+                    content: [1u8; 1024].as_slice()
+                },
+                PagesRange::Uncovered {
+                    from_page_no: 3,
+                    amount: 2
+                },
+                PagesRange::Covered {
+                    from_page_no: 5,
+                    content: [2u8; 512 * 3].as_slice()
+                },
+                PagesRange::Uncovered {
+                    from_page_no: 8,
+                    amount: 2
+                },
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn test_get_range_cuts_intervals_on_the_edges() {
+        let mut pages_cache = PagesCacheIntervals::new();
+        pages_cache.update_pages(1, vec![1u8; 512 * 3]);
+        pages_cache.update_pages(6, vec![2u8; 512 * 3]);
+
+        let result: Vec<PagesRange> = pages_cache.get_range(2, 5).collect();
+
+        assert_eq!(
+            vec![
+                PagesRange::Covered {
+                    from_page_no: 2,
+                    content: [1u8; 1024].as_slice()
+                },
+                PagesRange::Uncovered {
+                    from_page_no: 4,
+                    amount: 2
+                },
+                PagesRange::Covered {
+                    from_page_no: 6,
+                    content: [2u8; 512].as_slice()
+                },
+            ],
+            result
+        );
+
+        let result: Vec<PagesRange> = pages_cache.get_range(4, 2).collect();
+        assert_eq!(
+            vec![PagesRange::Uncovered {
+                from_page_no: 4,
+                amount: 2
+            }],
+            result
+        );
+
+        assert_eq!(0, pages_cache.get_range(3, 0).count());
+    }
+
+    #[test]
+    fn test_overwrite_in_the_middle_keeps_the_tail() {
+        let mut pages_cache = PagesCacheIntervals::new();
+
+        pages_cache.update_pages(1, vec![1u8; 512 * 4]);
+        pages_cache.update_pages(2, vec![2u8; 512]);
+        pages_cache.update_pages(0, vec![0u8; 1024]);
+
+        let mut result = vec![0u8; 1024];
+        result.extend_from_slice([2u8; 512].as_slice());
+        result.extend_from_slice([1u8; 512 * 2].as_slice());
+
+        assert_eq!(1, pages_cache.get_intervals_amount());
+        assert_eq!(0, get_items(&pages_cache)[0].page_id);
+        assert_eq!(result, get_items(&pages_cache)[0].content);
+    }
 }
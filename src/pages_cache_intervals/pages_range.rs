@@ -0,0 +1,101 @@
+use std::{collections::btree_map, iter::Peekable};
+
+use my_azure_storage_sdk::page_blob::consts::BLOB_PAGE_SIZE;
+
+use crate::PagesCacheItem;
+
+/// Part of the requested range of pages
+#[derive(Debug, PartialEq, Eq)]
+pub enum PagesRange<'s> {
+    Covered {
+        from_page_no: usize,
+        content: &'s [u8],
+    },
+    Uncovered {
+        from_page_no: usize,
+        amount: usize,
+    },
+}
+
+impl<'s> PagesRange<'s> {
+    pub fn get_from_page_no(&self) -> usize {
+        match self {
+            PagesRange::Covered { from_page_no, .. } => *from_page_no,
+            PagesRange::Uncovered { from_page_no, .. } => *from_page_no,
+        }
+    }
+
+    pub fn get_pages_amount(&self) -> usize {
+        match self {
+            PagesRange::Covered { content, .. } => content.len() / BLOB_PAGE_SIZE,
+            PagesRange::Uncovered { amount, .. } => *amount,
+        }
+    }
+}
+
+/// Walks the requested range of pages and yields covered and uncovered sub-ranges one after another
+pub struct PagesRangeIterator<'s> {
+    intervals: Peekable<btree_map::Range<'s, usize, PagesCacheItem>>,
+    page_no: usize,
+    end_page_no: usize,
+}
+
+impl<'s> PagesRangeIterator<'s> {
+    pub(crate) fn new(
+        intervals: btree_map::Range<'s, usize, PagesCacheItem>,
+        start_page_no: usize,
+        pages_amount: usize,
+    ) -> Self {
+        Self {
+            intervals: intervals.peekable(),
+            page_no: start_page_no,
+            end_page_no: start_page_no + pages_amount,
+        }
+    }
+}
+
+impl<'s> Iterator for PagesRangeIterator<'s> {
+    type Item = PagesRange<'s>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.page_no >= self.end_page_no {
+            return None;
+        }
+
+        let from_page_no = self.page_no;
+
+        // Intervals which end before the current page do not cover anything
+        while let Some((_, item)) = self.intervals.peek() {
+            if item.get_last_page_id() > from_page_no {
+                break;
+            }
+            self.intervals.next();
+        }
+
+        let Some((_, item)) = self.intervals.peek() else {
+            self.page_no = self.end_page_no;
+            return Some(PagesRange::Uncovered {
+                from_page_no,
+                amount: self.end_page_no - from_page_no,
+            });
+        };
+
+        if item.page_id > from_page_no {
+            self.page_no = item.page_id.min(self.end_page_no);
+            return Some(PagesRange::Uncovered {
+                from_page_no,
+                amount: self.page_no - from_page_no,
+            });
+        }
+
+        self.page_no = item.get_last_page_id().min(self.end_page_no);
+
+        let offset = (from_page_no - item.page_id) * BLOB_PAGE_SIZE;
+        let size = (self.page_no - from_page_no) * BLOB_PAGE_SIZE;
+
+        Some(PagesRange::Covered {
+            from_page_no,
+            content: &item.content[offset..offset + size],
+        })
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
 use my_azure_storage_sdk::page_blob::consts::BLOB_PAGE_SIZE;
 use rust_extensions::date_time::DateTimeAsMicroseconds;
 
@@ -5,9 +7,29 @@ pub struct CachedPage {
     payload: Vec<u8>,
     pub created: DateTimeAsMicroseconds,
     pub page_id: usize,
+    /// Order of insertion. Breaks ties between pages with the same rank
+    pub sequence: u64,
+    last_access: AtomicI64,
+    hits: AtomicU64,
 }
 
 impl CachedPage {
+    pub fn new(
+        page_id: usize,
+        payload: Vec<u8>,
+        created: DateTimeAsMicroseconds,
+        sequence: u64,
+    ) -> Self {
+        Self {
+            payload,
+            created,
+            page_id,
+            sequence,
+            last_access: AtomicI64::new(created.unix_microseconds),
+            hits: AtomicU64::new(0),
+        }
+    }
+
     pub fn get_payload(&self) -> &[u8] {
         &self.payload
     }
@@ -19,4 +41,20 @@ impl CachedPage {
     pub fn get_last_page_id(&self) -> usize {
         self.page_id + self.get_pages_amount()
     }
+
+    /// Registers the read of the page. Works under the shared reference, so reads do not need exclusive access to the cache.
+    /// Concurrent readers may come with their clocks in any order, so the last access never moves backwards
+    pub fn touch(&self, now: DateTimeAsMicroseconds) {
+        self.last_access
+            .fetch_max(now.unix_microseconds, Ordering::Relaxed);
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get_last_access(&self) -> DateTimeAsMicroseconds {
+        DateTimeAsMicroseconds::new(self.last_access.load(Ordering::Relaxed))
+    }
+
+    pub fn get_hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
 }
@@ -1,13 +1,27 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
 use my_azure_storage_sdk::page_blob::consts::BLOB_PAGE_SIZE;
 use rust_extensions::date_time::DateTimeAsMicroseconds;
 
-use super::{index_by_date::IndexByDate, CachedPage};
+use super::{index_by_date::IndexByDate, CachedPage, EvictionPolicy, FifoEvictionPolicy};
+
+struct IndexedPage {
+    page: Arc<CachedPage>,
+    /// Rank the page has in the eviction index
+    rank: i64,
+}
 
 pub struct CachedPagesList {
-    by_page_no: BTreeMap<usize, Arc<CachedPage>>,
+    by_page_no: BTreeMap<usize, IndexedPage>,
     index_by_date: IndexByDate,
+    /// Pages ordered by (rank, sequence). Reads raise ranks without exclusive access to the list,
+    /// so the rank of a page is refreshed once the page gets to the front of the index
+    eviction_index: BTreeSet<(i64, u64, usize)>,
+    eviction_policy: Arc<dyn EvictionPolicy>,
+    next_sequence: u64,
 
     max_pages_amount: usize,
     from_page_id: usize,
@@ -19,70 +33,222 @@ impl CachedPagesList {
         Self {
             by_page_no: BTreeMap::new(),
             index_by_date: IndexByDate::new(),
+            eviction_index: BTreeSet::new(),
+            eviction_policy: Arc::new(FifoEvictionPolicy),
+            next_sequence: 0,
             max_pages_amount,
             from_page_id,
             to_page_id,
         }
     }
 
+    pub fn with_eviction_policy(mut self, eviction_policy: Arc<dyn EvictionPolicy>) -> Self {
+        self.eviction_policy = eviction_policy;
+        self
+    }
+
+    pub fn get_from_page_id(&self) -> usize {
+        self.from_page_id
+    }
+
+    pub fn get_to_page_id(&self) -> usize {
+        self.to_page_id
+    }
+
+    pub fn is_my_page(&self, page_id: usize) -> bool {
+        page_id >= self.from_page_id && page_id <= self.to_page_id
+    }
+
+    pub fn has_my_pages(&self, from_page_id: usize, pages_amount: usize) -> bool {
+        pages_amount > 0
+            && from_page_id <= self.to_page_id
+            && from_page_id + pages_amount > self.from_page_id
+    }
+
     pub fn clear(&mut self) {
         self.by_page_no.clear();
         self.index_by_date.clear();
+        self.eviction_index.clear();
+    }
+
+    pub fn remove_pages_from(&mut self, page_no: usize) {
+        let removed_pages = self.by_page_no.split_off(&page_no);
+
+        for indexed_page in removed_pages.values() {
+            self.index_by_date.remove(&indexed_page.page);
+            self.eviction_index.remove(&get_eviction_key(indexed_page));
+        }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.by_page_no.len() * BLOB_PAGE_SIZE
+    }
+
+    fn add_page(&mut self, page: Arc<CachedPage>) {
+        if let Some(old_page) = self.by_page_no.remove(&page.page_id) {
+            self.index_by_date.remove(&old_page.page);
+            self.eviction_index.remove(&get_eviction_key(&old_page));
+        }
+
+        let indexed_page = IndexedPage {
+            rank: self.eviction_policy.rank(&page),
+            page: page.clone(),
+        };
+
+        self.eviction_index.insert(get_eviction_key(&indexed_page));
+        self.by_page_no.insert(page.page_id, indexed_page);
+        self.index_by_date.add(page);
     }
 
-    fn gc(&mut self) {
-        while self.by_page_no.len() > self.max_pages_amount {
+    fn remove_page(&mut self, page_id: usize) {
+        if let Some(indexed_page) = self.by_page_no.remove(&page_id) {
+            self.index_by_date.remove(&indexed_page.page);
+            self.eviction_index.remove(&get_eviction_key(&indexed_page));
+        }
+    }
+
+    /// Ranks only grow, so a page which is at the front with its actual rank is the one to evict
+    fn get_eviction_candidate(&mut self) -> Option<usize> {
+        loop {
+            let (rank, sequence, page_id) = *self.eviction_index.first()?;
+
+            let indexed_page = self.by_page_no.get_mut(&page_id)?;
+            let actual_rank = self.eviction_policy.rank(&indexed_page.page);
+
+            if actual_rank == rank {
+                return Some(page_id);
+            }
+
+            self.eviction_index.pop_first();
+            indexed_page.rank = actual_rank;
+            self.eviction_index.insert((actual_rank, sequence, page_id));
+        }
+    }
+
+    /// Last access of the page which is evicted next. Allows to choose between intervals with different policies
+    pub fn get_eviction_candidate_last_access(&mut self) -> Option<DateTimeAsMicroseconds> {
+        let page_id = self.get_eviction_candidate()?;
+        Some(self.by_page_no.get(&page_id)?.page.get_last_access())
+    }
+
+    /// Evicts the page chosen by the eviction policy. Returns false if there is nothing to evict
+    pub fn evict_one(&mut self) -> bool {
+        let Some(page_id) = self.get_eviction_candidate() else {
+            return false;
+        };
+
+        self.remove_page(page_id);
+        true
+    }
+
+    /// Pages are cached in order, so expired pages are the earliest ones. Returns amount of removed pages
+    fn remove_expired(&mut self, now: DateTimeAsMicroseconds) -> usize {
+        let mut removed = 0;
+
+        loop {
+            let Some(earliest) = self.index_by_date.get_earliest() else {
+                return removed;
+            };
+
+            let expired = earliest
+                .first()
+                .is_some_and(|page| self.eviction_policy.is_expired(page, now));
+
+            if !expired {
+                return removed;
+            }
+
             let Some(removed_pages) = self.index_by_date.remove_earliest() else {
-                break;
+                return removed;
             };
 
             for page in removed_pages {
-                self.by_page_no.remove(&page.page_id);
+                if let Some(indexed_page) = self.by_page_no.remove(&page.page_id) {
+                    self.eviction_index.remove(&get_eviction_key(&indexed_page));
+                }
+
+                removed += 1;
             }
         }
     }
 
-    pub fn insert(&mut self, page_no: usize, payload: Vec<u8>) {
+    /// Returns amount of evicted pages
+    fn gc(&mut self) -> usize {
+        let mut evicted = self.remove_expired(DateTimeAsMicroseconds::now());
+
+        while self.by_page_no.len() > self.max_pages_amount && self.evict_one() {
+            evicted += 1;
+        }
+
+        evicted
+    }
+
+    /// Returns amount of pages evicted to fit the pages budget
+    pub fn insert(&mut self, page_no: usize, payload: Vec<u8>) -> usize {
         let mut no = 0;
         for page_id in page_no..page_no + payload.len() / BLOB_PAGE_SIZE {
-            if page_id < self.from_page_id || page_id > self.to_page_id {
+            if !self.is_my_page(page_id) {
                 no += 1;
                 continue;
             }
 
             let offset = no * BLOB_PAGE_SIZE;
 
-            let new_page = CachedPage {
-                created: DateTimeAsMicroseconds::now(),
+            let new_page = CachedPage::new(
                 page_id,
-                payload: (&payload[offset..offset + BLOB_PAGE_SIZE]).to_vec(),
-            };
-
-            let new_page = Arc::new(new_page);
+                (&payload[offset..offset + BLOB_PAGE_SIZE]).to_vec(),
+                DateTimeAsMicroseconds::now(),
+                self.next_sequence,
+            );
 
-            let old_page = self.by_page_no.insert(new_page.page_id, new_page.clone());
+            self.next_sequence += 1;
 
-            if let Some(old_page) = old_page {
-                self.index_by_date.remove(&old_page);
-            }
-
-            self.index_by_date.add(new_page);
+            self.add_page(Arc::new(new_page));
 
             no += 1;
         }
 
-        self.gc();
+        self.gc()
     }
 
+    /// Counts the read for the eviction policy. Expired pages are not returned
     pub fn get_by_page_no(&self, page_no: usize) -> Option<&CachedPage> {
-        let result = self.by_page_no.get(&page_no)?;
+        let now = DateTimeAsMicroseconds::now();
+        let result = self.find_not_expired(page_no, now)?;
+        result.touch(now);
+        Some(result)
+    }
+
+    /// Same as get_by_page_no, but the read is not counted for the eviction policy
+    pub fn peek_by_page_no(&self, page_no: usize) -> Option<&CachedPage> {
+        self.find_not_expired(page_no, DateTimeAsMicroseconds::now())
+    }
+
+    fn find_not_expired(&self, page_no: usize, now: DateTimeAsMicroseconds) -> Option<&CachedPage> {
+        let result = &self.by_page_no.get(&page_no)?.page;
+
+        if self.eviction_policy.is_expired(result, now) {
+            return None;
+        }
+
         Some(result.as_ref())
     }
 }
 
+fn get_eviction_key(indexed_page: &IndexedPage) -> (i64, u64, usize) {
+    (
+        indexed_page.rank,
+        indexed_page.page.sequence,
+        indexed_page.page.page_id,
+    )
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{sync::Arc, time::Duration};
+
     use super::CachedPagesList;
+    use crate::pages_cache_list::{LfuEvictionPolicy, LruEvictionPolicy, TtlEvictionPolicy};
     use my_azure_storage_sdk::page_blob::consts::BLOB_PAGE_SIZE;
 
     #[test]
@@ -120,4 +286,126 @@ mod tests {
         assert!(cache.get_by_page_no(0).is_none());
         assert!(cache.get_by_page_no(1).is_some());
     }
+
+    #[test]
+    fn remove_pages_from_drops_tail() {
+        let mut cache = CachedPagesList::new(10, 0, 10);
+
+        cache.insert(0, vec![1u8; BLOB_PAGE_SIZE * 4]);
+        cache.remove_pages_from(2);
+
+        assert!(cache.get_by_page_no(1).is_some());
+        assert!(cache.get_by_page_no(2).is_none());
+        assert!(cache.get_by_page_no(3).is_none());
+    }
+
+    #[test]
+    fn lru_keeps_recently_read_pages() {
+        let mut cache =
+            CachedPagesList::new(2, 0, 10).with_eviction_policy(Arc::new(LruEvictionPolicy));
+
+        cache.insert(0, vec![1u8; BLOB_PAGE_SIZE]);
+        std::thread::sleep(Duration::from_millis(1));
+        cache.insert(1, vec![2u8; BLOB_PAGE_SIZE]);
+        std::thread::sleep(Duration::from_millis(1));
+
+        // Page 0 is the oldest one, but it is hot
+        assert!(cache.get_by_page_no(0).is_some());
+        std::thread::sleep(Duration::from_millis(1));
+
+        cache.insert(2, vec![3u8; BLOB_PAGE_SIZE]);
+
+        assert!(cache.get_by_page_no(0).is_some());
+        assert!(cache.get_by_page_no(1).is_none());
+        assert!(cache.get_by_page_no(2).is_some());
+    }
+
+    #[test]
+    fn lfu_keeps_frequently_read_pages() {
+        let mut cache =
+            CachedPagesList::new(2, 0, 10).with_eviction_policy(Arc::new(LfuEvictionPolicy));
+
+        cache.insert(0, vec![1u8; BLOB_PAGE_SIZE]);
+        cache.insert(1, vec![2u8; BLOB_PAGE_SIZE]);
+
+        cache.get_by_page_no(1);
+        cache.get_by_page_no(1);
+        cache.get_by_page_no(0);
+
+        cache.insert(2, vec![3u8; BLOB_PAGE_SIZE]);
+
+        // Page 2 has no hits yet, so it is evicted before page 0
+        assert!(cache.get_by_page_no(0).is_some());
+        assert!(cache.get_by_page_no(1).is_some());
+        assert!(cache.get_by_page_no(2).is_none());
+    }
+
+    #[test]
+    fn peeked_pages_are_not_counted_as_hits() {
+        let mut cache =
+            CachedPagesList::new(2, 0, 10).with_eviction_policy(Arc::new(LfuEvictionPolicy));
+
+        cache.insert(0, vec![1u8; BLOB_PAGE_SIZE]);
+        cache.insert(1, vec![2u8; BLOB_PAGE_SIZE]);
+
+        cache.get_by_page_no(0);
+        assert!(cache.peek_by_page_no(1).is_some());
+        assert!(cache.peek_by_page_no(1).is_some());
+
+        cache.insert(2, vec![3u8; BLOB_PAGE_SIZE]);
+
+        // Page 1 has no hits, so it goes before the newer page 2
+        assert!(cache.peek_by_page_no(0).is_some());
+        assert!(cache.peek_by_page_no(1).is_none());
+        assert!(cache.peek_by_page_no(2).is_some());
+    }
+
+    #[test]
+    fn ttl_expired_pages_are_not_served() {
+        let mut cache = CachedPagesList::new(10, 0, 10)
+            .with_eviction_policy(Arc::new(TtlEvictionPolicy::new(Duration::from_millis(20))));
+
+        cache.insert(0, vec![1u8; BLOB_PAGE_SIZE]);
+        assert!(cache.get_by_page_no(0).is_some());
+
+        std::thread::sleep(Duration::from_millis(30));
+
+        assert!(cache.get_by_page_no(0).is_none());
+
+        cache.insert(1, vec![2u8; BLOB_PAGE_SIZE]);
+
+        assert_eq!(BLOB_PAGE_SIZE, cache.get_size());
+    }
+
+    #[test]
+    fn eviction_index_follows_pages() {
+        let mut cache =
+            CachedPagesList::new(4, 0, 10).with_eviction_policy(Arc::new(LfuEvictionPolicy));
+
+        cache.insert(0, vec![1u8; BLOB_PAGE_SIZE * 4]);
+        cache.insert(1, vec![2u8; BLOB_PAGE_SIZE * 2]);
+        cache.remove_pages_from(3);
+
+        assert_eq!(cache.by_page_no.len(), cache.eviction_index.len());
+
+        cache.get_by_page_no(0);
+        cache.get_by_page_no(2);
+
+        // Page 1 is the only one which was not read
+        assert!(cache.evict_one());
+        assert!(cache.get_by_page_no(1).is_none());
+        assert_eq!(2, cache.eviction_index.len());
+
+        cache.clear();
+        assert!(cache.eviction_index.is_empty());
+        assert!(!cache.evict_one());
+    }
+
+    #[test]
+    fn insert_returns_evicted_amount() {
+        let mut cache = CachedPagesList::new(2, 0, 10);
+
+        assert_eq!(0, cache.insert(0, vec![1u8; BLOB_PAGE_SIZE * 2]));
+        assert_eq!(1, cache.insert(2, vec![2u8; BLOB_PAGE_SIZE]));
+    }
 }
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use rust_extensions::date_time::DateTimeAsMicroseconds;
+
+use super::CachedPage;
+
+/// Decides which pages are evicted once the interval exceeds its pages budget
+pub trait EvictionPolicy: Send + Sync {
+    /// Pages with the lowest rank are evicted first. Ties are broken by the insertion order
+    /// The rank of a page must not go down after the page is cached: reads may only raise it
+    fn rank(&self, page: &CachedPage) -> i64;
+
+    /// Expired pages are not served and are evicted regardless of the pages budget
+    fn is_expired(&self, _page: &CachedPage, _now: DateTimeAsMicroseconds) -> bool {
+        false
+    }
+}
+
+/// Evicts pages which were cached the earliest
+pub struct FifoEvictionPolicy;
+
+impl EvictionPolicy for FifoEvictionPolicy {
+    fn rank(&self, page: &CachedPage) -> i64 {
+        page.sequence as i64
+    }
+}
+
+/// Evicts pages which were read the least recently
+pub struct LruEvictionPolicy;
+
+impl EvictionPolicy for LruEvictionPolicy {
+    fn rank(&self, page: &CachedPage) -> i64 {
+        page.get_last_access().unix_microseconds
+    }
+}
+
+/// Evicts pages which were read the least amount of times
+pub struct LfuEvictionPolicy;
+
+impl EvictionPolicy for LfuEvictionPolicy {
+    fn rank(&self, page: &CachedPage) -> i64 {
+        page.get_hits() as i64
+    }
+}
+
+/// Pages live no longer than ttl since they were cached. Within the budget it works as FIFO
+pub struct TtlEvictionPolicy {
+    ttl: Duration,
+}
+
+impl TtlEvictionPolicy {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl }
+    }
+}
+
+impl EvictionPolicy for TtlEvictionPolicy {
+    fn rank(&self, page: &CachedPage) -> i64 {
+        page.created.unix_microseconds
+    }
+
+    fn is_expired(&self, page: &CachedPage, now: DateTimeAsMicroseconds) -> bool {
+        now.unix_microseconds - page.created.unix_microseconds >= self.ttl.as_micros() as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rust_extensions::date_time::DateTimeAsMicroseconds;
+
+    use super::*;
+
+    fn page(sequence: u64, created: i64) -> CachedPage {
+        CachedPage::new(
+            sequence as usize,
+            vec![],
+            DateTimeAsMicroseconds::new(created),
+            sequence,
+        )
+    }
+
+    #[test]
+    fn test_lru_rank_follows_last_access() {
+        let first = page(0, 10);
+        let second = page(1, 20);
+
+        first.touch(DateTimeAsMicroseconds::new(30));
+
+        assert!(LruEvictionPolicy.rank(&second) < LruEvictionPolicy.rank(&first));
+        assert!(FifoEvictionPolicy.rank(&first) < FifoEvictionPolicy.rank(&second));
+    }
+
+    #[test]
+    fn test_lfu_rank_follows_hits() {
+        let first = page(0, 10);
+        let second = page(1, 20);
+
+        first.touch(DateTimeAsMicroseconds::new(30));
+        first.touch(DateTimeAsMicroseconds::new(40));
+        second.touch(DateTimeAsMicroseconds::new(50));
+
+        assert!(LfuEvictionPolicy.rank(&second) < LfuEvictionPolicy.rank(&first));
+    }
+
+    #[test]
+    fn test_ttl_expiration() {
+        let policy = TtlEvictionPolicy::new(Duration::from_micros(100));
+        let page = page(0, 1_000);
+
+        assert!(!policy.is_expired(&page, DateTimeAsMicroseconds::new(1_099)));
+        assert!(policy.is_expired(&page, DateTimeAsMicroseconds::new(1_100)));
+    }
+}
@@ -27,6 +27,10 @@ impl IndexByDate {
         let key = page.created.unix_microseconds;
         let value = self.data.get_mut(&key).unwrap();
         value.retain(|x| x.page_id != page.page_id);
+
+        if value.is_empty() {
+            self.data.remove(&key);
+        }
     }
 
     pub fn clear(&mut self) {
@@ -41,6 +45,11 @@ impl IndexByDate {
         None
     }
 
+    pub fn get_earliest(&self) -> Option<&[Arc<CachedPage>]> {
+        let (_, pages) = self.data.iter().next()?;
+        Some(pages.as_slice())
+    }
+
     pub fn remove_earliest(&mut self) -> Option<Vec<Arc<CachedPage>>> {
         let microseconds = self.get_earliest_microseconds()?;
         self.data.remove(&microseconds)
@@ -61,11 +70,7 @@ mod tests {
     fn test_add_and_remove_index() {
         let mut index = IndexByDate::new();
 
-        let page = CachedPage {
-            created: DateTimeAsMicroseconds::new(5),
-            page_id: 0,
-            payload: vec![],
-        };
+        let page = CachedPage::new(0, vec![], DateTimeAsMicroseconds::new(5), 0);
 
         index.add(Arc::new(page));
 
@@ -80,17 +85,19 @@ mod tests {
     fn test_remove_earliest_returns_all_for_timestamp() {
         let mut index = IndexByDate::new();
 
-        let page_a = Arc::new(CachedPage {
-            created: DateTimeAsMicroseconds::new(5),
-            page_id: 1,
-            payload: vec![],
-        });
-
-        let page_b = Arc::new(CachedPage {
-            created: DateTimeAsMicroseconds::new(5),
-            page_id: 2,
-            payload: vec![],
-        });
+        let page_a = Arc::new(CachedPage::new(
+            1,
+            vec![],
+            DateTimeAsMicroseconds::new(5),
+            0,
+        ));
+
+        let page_b = Arc::new(CachedPage::new(
+            2,
+            vec![],
+            DateTimeAsMicroseconds::new(5),
+            1,
+        ));
 
         index.add(page_a.clone());
         index.add(page_b.clone());
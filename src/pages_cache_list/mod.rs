@@ -1,8 +1,10 @@
 mod cached_page;
 mod cached_pages_list;
+mod eviction_policy;
 mod index_by_date;
 mod pages_cache;
 pub use cached_pages_list::*;
 
 pub use cached_page::*;
+pub use eviction_policy::*;
 pub use pages_cache::PagesCache;
@@ -4,15 +4,30 @@ use super::{CachedPage, CachedPagesList};
 
 pub struct PagesCache {
     cached_pages: Vec<CachedPagesList>,
+    default_cache: Option<CachedPagesList>,
 }
 
 impl PagesCache {
     pub fn new() -> Self {
         Self {
             cached_pages: Vec::new(),
+            default_cache: None,
         }
     }
 
+    /// Caches pages which do not belong to any of the intervals added by add_interval_to_cache
+    pub fn set_default_interval(&mut self, max_pages_amount: usize) {
+        self.default_cache = Some(CachedPagesList::new(max_pages_amount, 0, usize::MAX));
+    }
+
+    pub fn set_default_cached_pages_list(&mut self, cached_pages_list: CachedPagesList) {
+        self.default_cache = Some(cached_pages_list);
+    }
+
+    pub fn add_cached_pages_list(&mut self, cached_pages_list: CachedPagesList) {
+        self.cached_pages.push(cached_pages_list);
+    }
+
     pub fn add_interval_to_cache(
         &mut self,
         from_page_id: usize,
@@ -26,23 +41,117 @@ impl PagesCache {
         ));
     }
 
-    pub fn update_cache(&mut self, start_page: usize, payload: &[u8]) {
+    /// Returns true if at least one page of the interval can be cached
+    pub fn can_cache(&self, from_page_no: usize, pages_amount: usize) -> bool {
+        if pages_amount == 0 {
+            return false;
+        }
+
+        self.default_cache.is_some()
+            || self
+                .cached_pages
+                .iter()
+                .any(|cache| cache.has_my_pages(from_page_no, pages_amount))
+    }
+
+    /// Returns amount of pages evicted to fit the budgets of intervals
+    pub fn update_cache(&mut self, start_page: usize, payload: &[u8]) -> usize {
         if payload.is_empty() {
-            return;
+            return 0;
         }
 
         // We expect payload to be page-aligned; partial pages are not cached.
         if payload.len() % BLOB_PAGE_SIZE != 0 {
-            return;
+            return 0;
+        }
+
+        let payload_vec = payload.to_vec();
+        let mut evicted = 0;
+
+        for cache in &mut self.cached_pages {
+            evicted += cache.insert(start_page, payload_vec.clone());
+        }
+
+        if let Some(default_cache) = &mut self.default_cache {
+            for (no, page) in payload.chunks(BLOB_PAGE_SIZE).enumerate() {
+                let page_id = start_page + no;
+
+                if self
+                    .cached_pages
+                    .iter()
+                    .any(|cache| cache.is_my_page(page_id))
+                {
+                    continue;
+                }
+
+                evicted += default_cache.insert(page_id, page.to_vec());
+            }
         }
 
-        let payload = payload.to_vec();
+        evicted
+    }
 
+    pub fn clear(&mut self) {
         for cache in &mut self.cached_pages {
-            cache.insert(start_page, payload.clone());
+            cache.clear();
+        }
+
+        if let Some(default_cache) = &mut self.default_cache {
+            default_cache.clear();
+        }
+    }
+
+    pub fn remove_pages_from(&mut self, page_no: usize) {
+        for cache in &mut self.cached_pages {
+            cache.remove_pages_from(page_no);
+        }
+
+        if let Some(default_cache) = &mut self.default_cache {
+            default_cache.remove_pages_from(page_no);
         }
     }
 
+    pub fn get_size(&self) -> usize {
+        let size: usize = self.cached_pages.iter().map(|cache| cache.get_size()).sum();
+        size + self
+            .default_cache
+            .as_ref()
+            .map_or(0, |cache| cache.get_size())
+    }
+
+    /// Evicts pages across all intervals until the cache fits max_size.
+    /// Returns amount of evicted pages
+    pub fn gc_to_size(&mut self, max_size: usize) -> usize {
+        let mut evicted = 0;
+        let mut size = self.get_size();
+
+        while size > max_size {
+            // Each interval picks the candidate by its own policy, the least recently read candidate is evicted
+            let candidate = self
+                .cached_pages
+                .iter_mut()
+                .chain(self.default_cache.as_mut())
+                .filter_map(|cache| {
+                    let last_access = cache.get_eviction_candidate_last_access()?;
+                    Some((last_access.unix_microseconds, cache))
+                })
+                .min_by_key(|(last_access, _)| *last_access);
+
+            let Some((_, cache)) = candidate else {
+                break;
+            };
+
+            if !cache.evict_one() {
+                break;
+            }
+
+            evicted += 1;
+            size -= BLOB_PAGE_SIZE;
+        }
+
+        evicted
+    }
+
     pub fn get(&self, page_no: usize) -> Option<&CachedPage> {
         for cache in &self.cached_pages {
             if let Some(page) = cache.get_by_page_no(page_no) {
@@ -50,7 +159,18 @@ impl PagesCache {
             }
         }
 
-        None
+        self.default_cache.as_ref()?.get_by_page_no(page_no)
+    }
+
+    /// Same as get, but the read is not counted for the eviction policy
+    pub fn peek(&self, page_no: usize) -> Option<&CachedPage> {
+        for cache in &self.cached_pages {
+            if let Some(page) = cache.peek_by_page_no(page_no) {
+                return Some(page);
+            }
+        }
+
+        self.default_cache.as_ref()?.peek_by_page_no(page_no)
     }
 }
 
@@ -129,4 +249,58 @@ mod tests {
             [5u8; BLOB_PAGE_SIZE].as_slice()
         );
     }
+
+    #[test]
+    fn test_default_interval_caches_pages_out_of_intervals() {
+        let mut cache = PagesCache::new();
+        cache.add_interval_to_cache(0, 1, 10);
+        cache.set_default_interval(10);
+
+        let payload = vec![6u8; BLOB_PAGE_SIZE * 4];
+        cache.update_cache(0, payload.as_slice());
+
+        for page_no in 0..4 {
+            assert_eq!(
+                cache.get(page_no).unwrap().get_payload(),
+                [6u8; BLOB_PAGE_SIZE].as_slice()
+            );
+        }
+    }
+
+    #[test]
+    fn test_default_interval_does_not_take_pages_of_intervals() {
+        let mut cache = PagesCache::new();
+        cache.add_interval_to_cache(0, 1, 1);
+        cache.set_default_interval(10);
+
+        let payload = vec![7u8; BLOB_PAGE_SIZE * 2];
+        cache.update_cache(0, payload.as_slice());
+
+        // Interval budget is 1 page, so one of the pages is evicted and default cache must not keep it
+        let cached_amount = (0..2)
+            .filter(|page_no| cache.get(*page_no).is_some())
+            .count();
+        assert!(cached_amount <= 1);
+    }
+
+    #[test]
+    fn test_gc_to_size_evicts_across_intervals() {
+        let mut cache = PagesCache::new();
+        cache.add_interval_to_cache(0, 9, 10);
+        cache.add_interval_to_cache(10, 19, 10);
+
+        cache.update_cache(0, vec![1u8; BLOB_PAGE_SIZE * 2].as_slice());
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        cache.update_cache(10, vec![2u8; BLOB_PAGE_SIZE * 2].as_slice());
+
+        assert_eq!(BLOB_PAGE_SIZE * 4, cache.get_size());
+
+        let evicted = cache.gc_to_size(BLOB_PAGE_SIZE * 2);
+
+        assert_eq!(2, evicted);
+        assert!(cache.get(0).is_none());
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(10).is_some());
+        assert!(cache.get(11).is_some());
+    }
 }
@@ -0,0 +1,60 @@
+use rust_extensions::date_time::DateTimeAsMicroseconds;
+
+/// xorshift64* generator. Good enough for jitter and fault injection, not for cryptography.
+pub struct PseudoRandom {
+    state: u64,
+}
+
+impl PseudoRandom {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            // Zero state makes xorshift produce zeros forever
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    pub fn from_time() -> Self {
+        Self::new(DateTimeAsMicroseconds::now().unix_microseconds as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns value in range [0..1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PseudoRandom;
+
+    #[test]
+    fn test_same_seed_gives_same_sequence() {
+        let mut a = PseudoRandom::new(42);
+        let mut b = PseudoRandom::new(42);
+
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn test_next_f64_is_in_range() {
+        let mut random = PseudoRandom::new(0);
+
+        for _ in 0..1000 {
+            let value = random.next_f64();
+            assert!(value >= 0.0 && value < 1.0);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::borrow::Cow;
+
+use my_azure_storage_sdk::AzureStorageError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageBlobOperation {
+    Resize,
+    CreateContainerIfNotExists,
+    Create,
+    CreateIfNotExists,
+    GetPages,
+    SavePages,
+    Delete,
+    Download,
+    GetBlobProperties,
+    /// Any other call executed by retry_async
+    Other(Cow<'static, str>),
+}
+
+impl PageBlobOperation {
+    pub fn as_str(&self) -> &str {
+        match self {
+            PageBlobOperation::Resize => "resize",
+            PageBlobOperation::CreateContainerIfNotExists => "create_container_if_not_exists",
+            PageBlobOperation::Create => "create",
+            PageBlobOperation::CreateIfNotExists => "create_if_not_exists",
+            PageBlobOperation::GetPages => "get_pages",
+            PageBlobOperation::SavePages => "save_pages",
+            PageBlobOperation::Delete => "delete",
+            PageBlobOperation::Download => "download",
+            PageBlobOperation::GetBlobProperties => "get_blob_properties",
+            PageBlobOperation::Other(name) => name.as_ref(),
+        }
+    }
+}
+
+impl From<&'static str> for PageBlobOperation {
+    fn from(name: &'static str) -> Self {
+        PageBlobOperation::Other(Cow::Borrowed(name))
+    }
+}
+
+impl From<String> for PageBlobOperation {
+    fn from(name: String) -> Self {
+        PageBlobOperation::Other(Cow::Owned(name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryAction {
+    CreateContainerIfNotExists,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryDecision {
+    Retry,
+    FailFast,
+    /// Runs the recovery action and retries the operation after the backoff delay
+    Recover(RecoveryAction),
+}
+
+pub trait RetryClassifier: Send + Sync {
+    fn classify(&self, operation: PageBlobOperation, err: &AzureStorageError) -> RetryDecision;
+}
+
+/// Errors which are going to be the same no matter how many times we retry
+pub fn is_permanent_error(err: &AzureStorageError) -> bool {
+    match err {
+        AzureStorageError::ContainerNotFound => true,
+        AzureStorageError::BlobNotFound => true,
+        AzureStorageError::BlobAlreadyExists => true,
+        AzureStorageError::ContainerAlreadyExists => true,
+        AzureStorageError::InvalidPageRange => true,
+        AzureStorageError::RequestBodyTooLarge => true,
+        AzureStorageError::InvalidResourceName => true,
+        _ => false,
+    }
+}
+
+/// HTTP status codes of the responses which can succeed if the request is sent again:
+/// request timeout, throttling and 5xx server errors
+const TRANSIENT_HTTP_STATUS_CODES: &[u16] = &[408, 429, 500, 502, 503, 504];
+
+/// Errors which can go away if we retry. Everything which is not known to be transient is treated as permanent
+pub fn is_transient_error(err: &AzureStorageError) -> bool {
+    match err {
+        AzureStorageError::Timeout => true,
+        AzureStorageError::UnknownError { msg } => match parse_http_status_code(msg) {
+            Some(status_code) => TRANSIENT_HTTP_STATUS_CODES.contains(&status_code),
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// Unknown errors of the SDK start with the HTTP status code of the response, e.g. "503 ServerBusy: ..."
+fn parse_http_status_code(msg: &str) -> Option<u16> {
+    let status_code = msg
+        .trim_start()
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?;
+
+    if status_code.len() != 3 {
+        return None;
+    }
+
+    let status_code: u16 = status_code.parse().ok()?;
+
+    if (100..600).contains(&status_code) {
+        Some(status_code)
+    } else {
+        None
+    }
+}
+
+/// Retries transient errors (timeouts, throttling, 5xx responses) and fails fast on the rest
+pub struct TransientErrorsRetryClassifier;
+
+impl RetryClassifier for TransientErrorsRetryClassifier {
+    fn classify(&self, _operation: PageBlobOperation, err: &AzureStorageError) -> RetryDecision {
+        if is_transient_error(err) {
+            return RetryDecision::Retry;
+        }
+
+        RetryDecision::FailFast
+    }
+}
+
+/// Same as TransientErrorsRetryClassifier, but creates missing container for the create operations
+pub struct AutoCreateContainerRetryClassifier;
+
+impl RetryClassifier for AutoCreateContainerRetryClassifier {
+    fn classify(&self, operation: PageBlobOperation, err: &AzureStorageError) -> RetryDecision {
+        if let AzureStorageError::ContainerNotFound = err {
+            match operation {
+                PageBlobOperation::Create | PageBlobOperation::CreateIfNotExists => {
+                    return RetryDecision::Recover(RecoveryAction::CreateContainerIfNotExists);
+                }
+                _ => {}
+            }
+        }
+
+        TransientErrorsRetryClassifier.classify(operation, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use my_azure_storage_sdk::AzureStorageError;
+
+    use super::*;
+
+    #[test]
+    fn test_permanent_errors_fail_fast() {
+        let classifier = TransientErrorsRetryClassifier;
+
+        assert_eq!(
+            RetryDecision::FailFast,
+            classifier.classify(
+                PageBlobOperation::GetPages,
+                &AzureStorageError::BlobNotFound
+            )
+        );
+
+        assert_eq!(
+            RetryDecision::FailFast,
+            classifier.classify(
+                PageBlobOperation::SavePages,
+                &AzureStorageError::InvalidPageRange
+            )
+        );
+    }
+
+    #[test]
+    fn test_timeout_is_retried() {
+        let classifier = TransientErrorsRetryClassifier;
+
+        assert_eq!(
+            RetryDecision::Retry,
+            classifier.classify(PageBlobOperation::GetPages, &AzureStorageError::Timeout)
+        );
+    }
+
+    #[test]
+    fn test_throttling_and_server_errors_are_retried() {
+        let classifier = TransientErrorsRetryClassifier;
+
+        let err = AzureStorageError::UnknownError {
+            msg: "503 ServerBusy: The server is currently unable to receive requests".to_string(),
+        };
+
+        assert_eq!(
+            RetryDecision::Retry,
+            classifier.classify(PageBlobOperation::SavePages, &err)
+        );
+    }
+
+    #[test]
+    fn test_auth_error_is_not_retried() {
+        let classifier = TransientErrorsRetryClassifier;
+
+        let err = AzureStorageError::UnknownError {
+            msg: "403 AuthenticationFailed: Server failed to authenticate the request".to_string(),
+        };
+
+        assert_eq!(
+            RetryDecision::FailFast,
+            classifier.classify(PageBlobOperation::GetPages, &err)
+        );
+
+        let err = AzureStorageError::UnknownError {
+            msg: "Unexpected response".to_string(),
+        };
+
+        assert_eq!(
+            RetryDecision::FailFast,
+            classifier.classify(PageBlobOperation::GetPages, &err)
+        );
+
+        // Status code is taken from the start of the message only
+        let err = AzureStorageError::UnknownError {
+            msg: "400 InvalidHeaderValue: Content-Length 5030".to_string(),
+        };
+
+        assert_eq!(
+            RetryDecision::FailFast,
+            classifier.classify(PageBlobOperation::GetPages, &err)
+        );
+    }
+
+    #[test]
+    fn test_missing_container_is_created_on_create() {
+        let classifier = AutoCreateContainerRetryClassifier;
+
+        assert_eq!(
+            RetryDecision::Recover(RecoveryAction::CreateContainerIfNotExists),
+            classifier.classify(
+                PageBlobOperation::Create,
+                &AzureStorageError::ContainerNotFound
+            )
+        );
+
+        assert_eq!(
+            RetryDecision::FailFast,
+            classifier.classify(
+                PageBlobOperation::GetPages,
+                &AzureStorageError::ContainerNotFound
+            )
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use my_azure_storage_sdk::AzureStorageError;
+
+use crate::{
+    BackoffPolicy, PageBlobOperation, RecoveryAction, RetryClassifier, RetryDecision,
+    TransientErrorsRetryClassifier,
+};
+
+#[derive(Clone)]
+pub struct RetryPolicy {
+    pub retries_amount: usize,
+    pub backoff: BackoffPolicy,
+    pub classifier: Arc<dyn RetryClassifier>,
+}
+
+impl RetryPolicy {
+    pub fn new(retries_amount: usize, backoff: BackoffPolicy) -> Self {
+        Self {
+            retries_amount,
+            backoff,
+            classifier: Arc::new(TransientErrorsRetryClassifier),
+        }
+    }
+
+    pub fn with_classifier(mut self, classifier: impl RetryClassifier + 'static) -> Self {
+        self.classifier = Arc::new(classifier);
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct RetryAttempt {
+    /// Starts from 1
+    pub attempt_no: usize,
+    pub error: String,
+    pub decision: RetryDecision,
+    /// Delay before the next attempt. Zero if there was no next attempt
+    pub delay: Duration,
+    /// Time passed since the first attempt has started
+    pub elapsed: Duration,
+}
+
+#[derive(Debug)]
+pub struct RetryError {
+    pub op_name: String,
+    pub error: AzureStorageError,
+    pub attempts: Vec<RetryAttempt>,
+}
+
+/// Executes operation and retries it according to the policy.
+/// RetryDecision::Recover is treated as RetryDecision::Retry since there is nothing to recover.
+/// Calls which are not page blob operations are passed by name and classified as PageBlobOperation::Other.
+pub async fn retry_async<TResult, TFuture, TOperation>(
+    policy: &RetryPolicy,
+    operation_kind: impl Into<PageBlobOperation>,
+    operation: TOperation,
+) -> Result<TResult, RetryError>
+where
+    TOperation: FnMut() -> TFuture,
+    TFuture: Future<Output = Result<TResult, AzureStorageError>>,
+{
+    retry_async_with_recovery(policy, operation_kind, operation, |_| async { Ok(()) }).await
+}
+
+/// Same as retry_async, but runs recover callback if classifier asks for the RecoveryAction
+pub async fn retry_async_with_recovery<TResult, TFuture, TOperation, TRecoveryFuture, TRecover>(
+    policy: &RetryPolicy,
+    operation_kind: impl Into<PageBlobOperation>,
+    mut operation: TOperation,
+    mut recover: TRecover,
+) -> Result<TResult, RetryError>
+where
+    TOperation: FnMut() -> TFuture,
+    TFuture: Future<Output = Result<TResult, AzureStorageError>>,
+    TRecover: FnMut(RecoveryAction) -> TRecoveryFuture,
+    TRecoveryFuture: Future<Output = Result<(), AzureStorageError>>,
+{
+    let operation_kind: PageBlobOperation = operation_kind.into();
+
+    let started = Instant::now();
+    let mut backoff = policy.backoff.start();
+    let mut attempts = Vec::new();
+
+    loop {
+        let err = match operation().await {
+            Ok(result) => return Ok(result),
+            Err(err) => err,
+        };
+
+        let decision = policy.classifier.classify(operation_kind.clone(), &err);
+
+        let delay = match decision {
+            RetryDecision::FailFast => None,
+            _ if backoff.get_attempt_no() >= policy.retries_amount => None,
+            RetryDecision::Retry | RetryDecision::Recover(_) => backoff.next_delay(),
+        };
+
+        attempts.push(RetryAttempt {
+            attempt_no: attempts.len() + 1,
+            error: format!("{:?}", err),
+            decision,
+            delay: delay.unwrap_or(Duration::ZERO),
+            elapsed: started.elapsed(),
+        });
+
+        let Some(delay) = delay else {
+            return Err(RetryError {
+                op_name: operation_kind.as_str().to_string(),
+                error: err,
+                attempts,
+            });
+        };
+
+        if let RetryDecision::Recover(action) = decision {
+            if let Err(err) = recover(action).await {
+                return Err(RetryError {
+                    op_name: operation_kind.as_str().to_string(),
+                    error: err,
+                    attempts,
+                });
+            }
+        }
+
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use my_azure_storage_sdk::AzureStorageError;
+
+    use super::*;
+    use crate::{AutoCreateContainerRetryClassifier, BackoffPolicy, PageBlobOperation};
+
+    fn policy(retries_amount: usize) -> RetryPolicy {
+        RetryPolicy::new(retries_amount, BackoffPolicy::fixed(Duration::ZERO))
+    }
+
+    #[tokio::test]
+    async fn test_transient_error_is_retried_until_success() {
+        let calls = AtomicUsize::new(0);
+
+        let result = retry_async(&policy(3), "test", || async {
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                return Err(AzureStorageError::Timeout);
+            }
+            Ok(5)
+        })
+        .await;
+
+        assert_eq!(5, result.unwrap());
+        assert_eq!(3, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_attempts_history_is_returned() {
+        let calls = AtomicUsize::new(0);
+
+        let result: Result<(), RetryError> = retry_async(&policy(2), "test", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(AzureStorageError::Timeout)
+        })
+        .await;
+
+        let err = result.unwrap_err();
+
+        assert_eq!("test", err.op_name);
+        assert_eq!(3, err.attempts.len());
+        assert_eq!(3, calls.load(Ordering::SeqCst));
+        assert_eq!(3, err.attempts.last().unwrap().attempt_no);
+    }
+
+    #[tokio::test]
+    async fn test_operation_name_can_be_built_at_runtime() {
+        let container_name = "container";
+
+        let result: Result<(), RetryError> = retry_async(
+            &policy(0),
+            format!("list_blobs:{}", container_name),
+            || async { Err(AzureStorageError::Timeout) },
+        )
+        .await;
+
+        assert_eq!("list_blobs:container", result.unwrap_err().op_name);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_error_fails_fast() {
+        let calls = AtomicUsize::new(0);
+
+        let result: Result<(), RetryError> = retry_async(&policy(5), "test", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(AzureStorageError::BlobNotFound)
+        })
+        .await;
+
+        let err = result.unwrap_err();
+
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+        assert_eq!(1, err.attempts.len());
+        assert_eq!(RetryDecision::FailFast, err.attempts[0].decision);
+    }
+
+    #[tokio::test]
+    async fn test_recovery_is_executed_before_retry() {
+        let calls = AtomicUsize::new(0);
+        let recoveries = AtomicUsize::new(0);
+
+        let policy = policy(3).with_classifier(AutoCreateContainerRetryClassifier);
+
+        let result = retry_async_with_recovery(
+            &policy,
+            PageBlobOperation::Create,
+            || async {
+                if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return Err(AzureStorageError::ContainerNotFound);
+                }
+                Ok(())
+            },
+            |_| async {
+                recoveries.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            },
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+        assert_eq!(1, recoveries.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_recovery_is_followed_by_backoff_delay() {
+        let policy = RetryPolicy::new(2, BackoffPolicy::fixed(Duration::from_millis(50)))
+            .with_classifier(AutoCreateContainerRetryClassifier);
+
+        let started = std::time::Instant::now();
+
+        let result: Result<(), RetryError> =
+            retry_async(&policy, PageBlobOperation::Create, || async {
+                Err(AzureStorageError::ContainerNotFound)
+            })
+            .await;
+
+        assert_eq!(3, result.unwrap_err().attempts.len());
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,483 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use my_azure_storage_sdk::{page_blob::consts::BLOB_PAGE_SIZE, AzureStorageError};
+use tokio::sync::watch;
+
+#[derive(Clone)]
+enum InFlightReadState {
+    Pending,
+    Done(Arc<Vec<u8>>),
+    Failed,
+}
+
+struct InFlightRead {
+    id: u64,
+    pages_amount: usize,
+    generation: u64,
+    receiver: watch::Receiver<InFlightReadState>,
+}
+
+enum ReadSegment {
+    /// Pages are being downloaded by another caller
+    Subscribed {
+        from_page_no: usize,
+        pages_amount: usize,
+        download_from_page_no: usize,
+        receiver: watch::Receiver<InFlightReadState>,
+    },
+    /// Pages are downloaded by us and shared with the callers who come meanwhile
+    Owned {
+        from_page_no: usize,
+        pages_amount: usize,
+        id: u64,
+        sender: watch::Sender<InFlightReadState>,
+    },
+}
+
+/// Table of page ranges which are being downloaded right now.
+/// Callers which ask for pages which are already being downloaded wait for that download instead of issuing another one.
+/// If the shared download fails, each waiter downloads pages by itself, so every caller gets its own error.
+///
+/// Every read comes with the generation of the content the caller has seen (e.g. the amount of writes done before the read).
+/// A download is shared only with callers of the same or older generation, so a read which starts after a write
+/// never gets the content downloaded before that write.
+pub struct SingleFlightReads {
+    in_flight: Mutex<BTreeMap<usize, InFlightRead>>,
+    next_id: AtomicU64,
+}
+
+impl SingleFlightReads {
+    pub fn new() -> Self {
+        Self {
+            in_flight: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get_in_flight_amount(&self) -> usize {
+        self.in_flight.lock().unwrap().len()
+    }
+
+    pub async fn get_pages<TFuture>(
+        &self,
+        start_page_no: usize,
+        pages_amount: usize,
+        generation: u64,
+        download: impl Fn(usize, usize) -> TFuture,
+    ) -> Result<Vec<u8>, AzureStorageError>
+    where
+        TFuture: Future<Output = Result<Vec<u8>, AzureStorageError>>,
+    {
+        if pages_amount == 0 {
+            return download(start_page_no, pages_amount).await;
+        }
+
+        let segments = self.split_into_segments(start_page_no, pages_amount, generation);
+
+        let download = &download;
+        let results = futures::future::try_join_all(
+            segments
+                .into_iter()
+                .map(|segment| self.read_segment(segment, download)),
+        )
+        .await?;
+
+        let mut result = Vec::with_capacity(pages_amount * BLOB_PAGE_SIZE);
+
+        for segment_result in results {
+            result.extend_from_slice(segment_result.as_slice());
+        }
+
+        Ok(result)
+    }
+
+    fn split_into_segments(
+        &self,
+        start_page_no: usize,
+        pages_amount: usize,
+        generation: u64,
+    ) -> Vec<ReadSegment> {
+        let end_page_no = start_page_no + pages_amount;
+
+        let mut in_flight = self.in_flight.lock().unwrap();
+
+        let mut overlapped_from_pages = Vec::new();
+
+        // The read which started before our range can still cover its beginning
+        if let Some((from_page_no, read)) = in_flight.range(..start_page_no).next_back() {
+            if from_page_no + read.pages_amount > start_page_no {
+                overlapped_from_pages.push(*from_page_no);
+            }
+        }
+
+        overlapped_from_pages.extend(
+            in_flight
+                .range(start_page_no..end_page_no)
+                .map(|(from_page_no, _)| *from_page_no),
+        );
+
+        let mut overlapped = Vec::new();
+
+        for from_page_no in overlapped_from_pages {
+            let read = in_flight.get(&from_page_no).unwrap();
+
+            if read.generation < generation {
+                // Download started before the content we expect was written. Nobody else should join it either
+                in_flight.remove(&from_page_no);
+                continue;
+            }
+
+            overlapped.push((from_page_no, read.pages_amount, read.receiver.clone()));
+        }
+
+        let mut segments = Vec::new();
+        let mut page_no = start_page_no;
+
+        for (download_from_page_no, download_pages_amount, receiver) in overlapped {
+            if download_from_page_no > page_no {
+                segments.push(self.own_segment(
+                    &mut in_flight,
+                    page_no,
+                    download_from_page_no - page_no,
+                    generation,
+                ));
+                page_no = download_from_page_no;
+            }
+
+            let to_page_no = (download_from_page_no + download_pages_amount).min(end_page_no);
+
+            segments.push(ReadSegment::Subscribed {
+                from_page_no: page_no,
+                pages_amount: to_page_no - page_no,
+                download_from_page_no,
+                receiver,
+            });
+
+            page_no = to_page_no;
+        }
+
+        if page_no < end_page_no {
+            segments.push(self.own_segment(
+                &mut in_flight,
+                page_no,
+                end_page_no - page_no,
+                generation,
+            ));
+        }
+
+        segments
+    }
+
+    fn own_segment(
+        &self,
+        in_flight: &mut BTreeMap<usize, InFlightRead>,
+        from_page_no: usize,
+        pages_amount: usize,
+        generation: u64,
+    ) -> ReadSegment {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = watch::channel(InFlightReadState::Pending);
+
+        in_flight.insert(
+            from_page_no,
+            InFlightRead {
+                id,
+                pages_amount,
+                generation,
+                receiver,
+            },
+        );
+
+        ReadSegment::Owned {
+            from_page_no,
+            pages_amount,
+            id,
+            sender,
+        }
+    }
+
+    async fn read_segment<TFuture>(
+        &self,
+        segment: ReadSegment,
+        download: &impl Fn(usize, usize) -> TFuture,
+    ) -> Result<Vec<u8>, AzureStorageError>
+    where
+        TFuture: Future<Output = Result<Vec<u8>, AzureStorageError>>,
+    {
+        match segment {
+            ReadSegment::Subscribed {
+                from_page_no,
+                pages_amount,
+                download_from_page_no,
+                mut receiver,
+            } => {
+                let state = receiver
+                    .wait_for(|state| !matches!(state, InFlightReadState::Pending))
+                    .await
+                    .map(|state| state.clone());
+
+                if let Ok(InFlightReadState::Done(payload)) = state {
+                    let offset = (from_page_no - download_from_page_no) * BLOB_PAGE_SIZE;
+                    let size = pages_amount * BLOB_PAGE_SIZE;
+
+                    if offset + size <= payload.len() {
+                        return Ok(payload[offset..offset + size].to_vec());
+                    }
+                }
+
+                // Shared download failed or was cancelled
+                download(from_page_no, pages_amount).await
+            }
+            ReadSegment::Owned {
+                from_page_no,
+                pages_amount,
+                id,
+                sender,
+            } => {
+                // Removes the read from the table even if the future is dropped
+                let in_flight_guard = InFlightGuard {
+                    single_flight_reads: self,
+                    from_page_no,
+                    id,
+                };
+
+                let result = download(from_page_no, pages_amount).await;
+
+                // Nobody can subscribe after the read is removed, so the receivers left are the waiting callers
+                drop(in_flight_guard);
+
+                match result {
+                    Ok(payload) => {
+                        if sender.receiver_count() == 0 {
+                            return Ok(payload);
+                        }
+
+                        let payload = Arc::new(payload);
+                        let _ = sender.send(InFlightReadState::Done(payload.clone()));
+                        Ok(payload.as_ref().clone())
+                    }
+                    Err(err) => {
+                        let _ = sender.send(InFlightReadState::Failed);
+                        Err(err)
+                    }
+                }
+            }
+        }
+    }
+
+    fn remove(&self, from_page_no: usize, id: u64) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+
+        if let Some(read) = in_flight.get(&from_page_no) {
+            if read.id == id {
+                in_flight.remove(&from_page_no);
+            }
+        }
+    }
+}
+
+struct InFlightGuard<'s> {
+    single_flight_reads: &'s SingleFlightReads,
+    from_page_no: usize,
+    id: u64,
+}
+
+impl<'s> Drop for InFlightGuard<'s> {
+    fn drop(&mut self) {
+        self.single_flight_reads.remove(self.from_page_no, self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use my_azure_storage_sdk::{page_blob::consts::BLOB_PAGE_SIZE, AzureStorageError};
+
+    use super::SingleFlightReads;
+
+    fn get_content(from_page_no: usize, pages_amount: usize) -> Vec<u8> {
+        let mut result = Vec::with_capacity(pages_amount * BLOB_PAGE_SIZE);
+
+        for page_no in from_page_no..from_page_no + pages_amount {
+            result.extend_from_slice([page_no as u8; BLOB_PAGE_SIZE].as_slice());
+        }
+
+        result
+    }
+
+    async fn download(
+        downloads: &AtomicUsize,
+        from_page_no: usize,
+        pages_amount: usize,
+    ) -> Result<Vec<u8>, AzureStorageError> {
+        downloads.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(get_content(from_page_no, pages_amount))
+    }
+
+    #[tokio::test]
+    async fn test_same_range_is_downloaded_once() {
+        let single_flight_reads = Arc::new(SingleFlightReads::new());
+        let downloads = Arc::new(AtomicUsize::new(0));
+
+        let mut tasks = Vec::new();
+
+        for _ in 0..5 {
+            let single_flight_reads = single_flight_reads.clone();
+            let downloads = downloads.clone();
+
+            tasks.push(tokio::spawn(async move {
+                single_flight_reads
+                    .get_pages(3, 2, 0, |from_page_no, pages_amount| {
+                        download(&downloads, from_page_no, pages_amount)
+                    })
+                    .await
+            }));
+        }
+
+        for task in tasks {
+            assert_eq!(get_content(3, 2), task.await.unwrap().unwrap());
+        }
+
+        assert_eq!(1, downloads.load(Ordering::SeqCst));
+        assert_eq!(0, single_flight_reads.get_in_flight_amount());
+    }
+
+    #[tokio::test]
+    async fn test_partial_overlap_downloads_only_gaps() {
+        let single_flight_reads = Arc::new(SingleFlightReads::new());
+        let downloads = Arc::new(AtomicUsize::new(0));
+
+        let first = {
+            let single_flight_reads = single_flight_reads.clone();
+            let downloads = downloads.clone();
+            tokio::spawn(async move {
+                single_flight_reads
+                    .get_pages(2, 3, 0, |from_page_no, pages_amount| {
+                        download(&downloads, from_page_no, pages_amount)
+                    })
+                    .await
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // Pages 0..2 and 5..7 are gaps, pages 2..5 are shared with the first read
+        let result = single_flight_reads
+            .get_pages(0, 7, 0, |from_page_no, pages_amount| {
+                download(&downloads, from_page_no, pages_amount)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(get_content(0, 7), result);
+        assert_eq!(get_content(2, 3), first.await.unwrap().unwrap());
+        assert_eq!(3, downloads.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_waiters_download_by_themselves_if_shared_download_fails() {
+        let single_flight_reads = Arc::new(SingleFlightReads::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let download = {
+            let calls = calls.clone();
+            move |from_page_no: usize, pages_amount: usize| {
+                let call_no = calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    if call_no == 0 {
+                        return Err(AzureStorageError::Timeout);
+                    }
+                    Ok(get_content(from_page_no, pages_amount))
+                }
+            }
+        };
+
+        let first = {
+            let single_flight_reads = single_flight_reads.clone();
+            let download = download.clone();
+            tokio::spawn(async move { single_flight_reads.get_pages(0, 1, 0, download).await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let second = single_flight_reads.get_pages(0, 1, 0, download).await;
+
+        assert!(first.await.unwrap().is_err());
+        assert_eq!(get_content(0, 1), second.unwrap());
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_newer_generation_does_not_join_older_download() {
+        let single_flight_reads = Arc::new(SingleFlightReads::new());
+        let downloads = Arc::new(AtomicUsize::new(0));
+
+        let old_read = {
+            let single_flight_reads = single_flight_reads.clone();
+            let downloads = downloads.clone();
+            tokio::spawn(async move {
+                single_flight_reads
+                    .get_pages(0, 4, 1, |from_page_no, pages_amount| {
+                        download(&downloads, from_page_no, pages_amount)
+                    })
+                    .await
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // The caller has seen a write which the running download can miss
+        single_flight_reads
+            .get_pages(1, 2, 2, |from_page_no, pages_amount| {
+                download(&downloads, from_page_no, pages_amount)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(2, downloads.load(Ordering::SeqCst));
+
+        // Older callers can join the newer download
+        let newer_read = {
+            let single_flight_reads = single_flight_reads.clone();
+            let downloads = downloads.clone();
+            tokio::spawn(async move {
+                single_flight_reads
+                    .get_pages(5, 1, 3, |from_page_no, pages_amount| {
+                        download(&downloads, from_page_no, pages_amount)
+                    })
+                    .await
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        single_flight_reads
+            .get_pages(5, 1, 0, |from_page_no, pages_amount| {
+                download(&downloads, from_page_no, pages_amount)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(3, downloads.load(Ordering::SeqCst));
+
+        old_read.await.unwrap().unwrap();
+        newer_read.await.unwrap().unwrap();
+        assert_eq!(0, single_flight_reads.get_in_flight_amount());
+    }
+}
@@ -0,0 +1,197 @@
+use my_azure_storage_sdk::{page_blob::consts::BLOB_PAGE_SIZE, AzureStorageError};
+use my_telemetry::{MyTelemetryContext, TELEMETRY_INTERFACE};
+use rust_extensions::date_time::DateTimeAsMicroseconds;
+
+use crate::PageBlobOperation;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheLookup {
+    Hit,
+    PartialHit,
+    Miss,
+}
+
+impl CacheLookup {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheLookup::Hit => "hit",
+            CacheLookup::PartialHit => "partial_hit",
+            CacheLookup::Miss => "miss",
+        }
+    }
+}
+
+/// Event is written with the started moment, so telemetry calculates the latency of the operation
+pub struct PageBlobTelemetryEvent {
+    pub operation: PageBlobOperation,
+    pub started: DateTimeAsMicroseconds,
+    pub from_page_no: Option<usize>,
+    pub pages_amount: Option<usize>,
+    pub bytes: Option<usize>,
+    pub attempts: usize,
+    pub cache_lookup: Option<CacheLookup>,
+}
+
+impl PageBlobTelemetryEvent {
+    pub fn new(operation: impl Into<PageBlobOperation>) -> Self {
+        Self {
+            operation: operation.into(),
+            started: DateTimeAsMicroseconds::now(),
+            from_page_no: None,
+            pages_amount: None,
+            bytes: None,
+            attempts: 1,
+            cache_lookup: None,
+        }
+    }
+
+    pub fn with_pages(mut self, from_page_no: usize, pages_amount: usize) -> Self {
+        self.from_page_no = Some(from_page_no);
+        self.pages_amount = Some(pages_amount);
+        self.bytes = Some(pages_amount * BLOB_PAGE_SIZE);
+        self
+    }
+
+    pub fn with_bytes(mut self, bytes: usize) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+
+    pub fn with_cache_lookup(mut self, cache_lookup: CacheLookup) -> Self {
+        self.cache_lookup = Some(cache_lookup);
+        self
+    }
+
+    pub fn format_data(&self, container_name: &str, blob_name: &str) -> String {
+        let mut result = format!(
+            "PageBlob {} {}/{}",
+            self.operation.as_str(),
+            container_name,
+            blob_name
+        );
+
+        if let (Some(from_page_no), Some(pages_amount)) = (self.from_page_no, self.pages_amount) {
+            result.push_str(&format!(
+                " pages {}..{}",
+                from_page_no,
+                from_page_no + pages_amount
+            ));
+        }
+
+        result
+    }
+
+    pub fn format_details(&self) -> String {
+        let mut result = format!("attempts: {}", self.attempts);
+
+        if let Some(bytes) = self.bytes {
+            result.push_str(&format!(", bytes: {}", bytes));
+        }
+
+        if let Some(cache_lookup) = self.cache_lookup {
+            result.push_str(&format!(", cache: {}", cache_lookup.as_str()));
+        }
+
+        result
+    }
+}
+
+pub async fn write_telemetry_event(
+    ctx: Option<&MyTelemetryContext>,
+    container_name: &str,
+    blob_name: &str,
+    event: PageBlobTelemetryEvent,
+    err: Option<&AzureStorageError>,
+) {
+    #[cfg(test)]
+    record_written_event(container_name, blob_name, &event);
+
+    let Some(ctx) = ctx else {
+        return;
+    };
+
+    if !TELEMETRY_INTERFACE.is_telemetry_set_up() {
+        return;
+    }
+
+    let data = event.format_data(container_name, blob_name);
+
+    match err {
+        Some(err) => {
+            TELEMETRY_INTERFACE
+                .write_fail(
+                    ctx,
+                    event.started,
+                    data,
+                    format!("{:?}. {}", err, event.format_details()),
+                    None,
+                )
+                .await;
+        }
+        None => {
+            TELEMETRY_INTERFACE
+                .write_success(ctx, event.started, data, event.format_details(), None)
+                .await;
+        }
+    }
+}
+
+/// Events written by all the tests. Tests use unique blob names to find their events
+#[cfg(test)]
+static WRITTEN_EVENTS: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+#[cfg(test)]
+fn record_written_event(container_name: &str, blob_name: &str, event: &PageBlobTelemetryEvent) {
+    WRITTEN_EVENTS.lock().unwrap().push((
+        format!("{}/{}", container_name, blob_name),
+        event.operation.as_str().to_string(),
+    ));
+}
+
+/// Names of the operations written for the blob, in the order they were written
+#[cfg(test)]
+pub(crate) fn get_written_operations(container_name: &str, blob_name: &str) -> Vec<String> {
+    let blob_path = format!("{}/{}", container_name, blob_name);
+
+    WRITTEN_EVENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(path, _)| path == &blob_path)
+        .map(|(_, operation)| operation.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_event_with_pages() {
+        let mut event = PageBlobTelemetryEvent::new(PageBlobOperation::GetPages)
+            .with_pages(10, 2)
+            .with_cache_lookup(CacheLookup::PartialHit);
+        event.attempts = 3;
+
+        assert_eq!(
+            "PageBlob get_pages container/blob pages 10..12",
+            event.format_data("container", "blob")
+        );
+
+        assert_eq!(
+            "attempts: 3, bytes: 1024, cache: partial_hit",
+            event.format_details()
+        );
+    }
+
+    #[test]
+    fn test_format_event_without_pages() {
+        let event = PageBlobTelemetryEvent::new(PageBlobOperation::Delete);
+
+        assert_eq!(
+            "PageBlob delete container/blob",
+            event.format_data("container", "blob")
+        );
+        assert_eq!("attempts: 1", event.format_details());
+    }
+}
@@ -29,11 +29,43 @@ pub fn fill_content_to_comply_with_page_blob_size(
     }
 }
 
+/// Azure rejects Put Page requests with a body bigger than 4 MiB
+pub const MAX_PUT_PAGES_SIZE: usize = 4 * 1024 * 1024;
+
+pub struct PagesChunk<'s> {
+    pub start_page_no: usize,
+    pub payload: &'s [u8],
+}
+
+impl<'s> PagesChunk<'s> {
+    pub fn get_pages_amount(&self, page_size: usize) -> usize {
+        self.payload.len() / page_size
+    }
+}
+
+/// Splits page aligned payload into page aligned chunks which are not bigger than max_chunk_size
+pub fn split_into_pages_chunks<'s>(
+    start_page_no: usize,
+    payload: &'s [u8],
+    page_size: usize,
+    max_chunk_size: usize,
+) -> impl Iterator<Item = PagesChunk<'s>> {
+    let pages_in_chunk = (max_chunk_size / page_size).max(1);
+
+    payload
+        .chunks(pages_in_chunk * page_size)
+        .enumerate()
+        .map(move |(no, payload)| PagesChunk {
+            start_page_no: start_page_no + no * pages_in_chunk,
+            payload,
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use my_azure_storage_sdk::page_blob::consts::BLOB_PAGE_SIZE;
 
-    use super::fill_content_to_comply_with_page_blob_size;
+    use super::{fill_content_to_comply_with_page_blob_size, split_into_pages_chunks};
 
     #[test]
     fn test_page_blob_resize() {
@@ -66,4 +98,58 @@ mod tests {
     fn calc_pages_amount_to_ressize_handles_zero_position() {
         assert_eq!(0, super::calc_pages_amount_to_ressize(0, BLOB_PAGE_SIZE, 4));
     }
+
+    #[test]
+    fn split_into_pages_chunks_keeps_small_payload_as_is() {
+        let payload = vec![1u8; BLOB_PAGE_SIZE * 3];
+
+        let chunks: Vec<_> =
+            split_into_pages_chunks(10, payload.as_slice(), BLOB_PAGE_SIZE, BLOB_PAGE_SIZE * 4)
+                .collect();
+
+        assert_eq!(1, chunks.len());
+        assert_eq!(10, chunks[0].start_page_no);
+        assert_eq!(3, chunks[0].get_pages_amount(BLOB_PAGE_SIZE));
+    }
+
+    #[test]
+    fn split_into_pages_chunks_splits_big_payload() {
+        let payload = vec![1u8; BLOB_PAGE_SIZE * 5];
+
+        let chunks: Vec<_> =
+            split_into_pages_chunks(10, payload.as_slice(), BLOB_PAGE_SIZE, BLOB_PAGE_SIZE * 2)
+                .collect();
+
+        assert_eq!(3, chunks.len());
+
+        assert_eq!(10, chunks[0].start_page_no);
+        assert_eq!(2, chunks[0].get_pages_amount(BLOB_PAGE_SIZE));
+
+        assert_eq!(12, chunks[1].start_page_no);
+        assert_eq!(2, chunks[1].get_pages_amount(BLOB_PAGE_SIZE));
+
+        assert_eq!(14, chunks[2].start_page_no);
+        assert_eq!(1, chunks[2].get_pages_amount(BLOB_PAGE_SIZE));
+    }
+
+    #[test]
+    fn split_into_pages_chunks_aligns_chunk_size_to_pages() {
+        let payload = vec![1u8; super::MAX_PUT_PAGES_SIZE * 2];
+
+        let chunks: Vec<_> = split_into_pages_chunks(
+            0,
+            payload.as_slice(),
+            BLOB_PAGE_SIZE,
+            super::MAX_PUT_PAGES_SIZE - 1,
+        )
+        .collect();
+
+        for chunk in &chunks {
+            assert_eq!(0, chunk.payload.len() % BLOB_PAGE_SIZE);
+            assert!(chunk.payload.len() < super::MAX_PUT_PAGES_SIZE);
+        }
+
+        let total: usize = chunks.iter().map(|chunk| chunk.payload.len()).sum();
+        assert_eq!(payload.len(), total);
+    }
 }